@@ -0,0 +1,167 @@
+use anyhow::anyhow;
+use std::collections::HashMap;
+
+/// Command line: `rosario [command] [--option=value1,value2]...`
+#[derive(Default, Debug)]
+pub(crate) struct Args {
+    pub(crate) command: Option<String>,
+    options: HashMap<String, Vec<String>>,
+}
+
+impl Args {
+    /// All values of `--name`, both repeated and comma separated ones.
+    pub(crate) fn values(&self, name: &str) -> Vec<String> {
+        self.options.get(name).cloned().unwrap_or_default()
+    }
+
+    /// Whether `--name` is given at all, with or without values.
+    pub(crate) fn flag(&self, name: &str) -> bool {
+        self.options.contains_key(name)
+    }
+}
+
+/// Printed when the command line is rejected.
+pub(crate) const USAGE: &str = "\
+usage: rosario [command] [--option=value1,value2]...
+
+commands:
+  (none)          crawl the tag listings
+  proxy           fetch and check the proxy pool, run it first
+  retry-failed    process the urls failed in previous runs again
+  search          crawl the books found by --keywords, --isbns or --authors, files with a query per line
+  record-goldens  re-record the parser output of the saved pages in --fixtures, tests/fixtures by default
+
+options:
+  --profile=path --ignore-robots=host,.. --download-covers --block-on[=rule,..] --ignore-drift
+  --categories=name,.. --exclude-categories=name,.. --tags=name,.. --exclude-tags=name,..
+  --min-tag-count=n --sort=T,S,R --follow-related --all-comments
+  --keywords=path --isbns=path --authors=path --fixtures=path";
+
+const COMMANDS: &[&str] = &["proxy", "retry-failed", "search", "record-goldens"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OptionValue {
+    /// a flag, `--name` alone
+    None,
+    Required,
+    /// `--name` alone is the empty list
+    Optional,
+}
+
+const OPTIONS: &[(&str, OptionValue)] = &[
+    ("profile", OptionValue::Required),
+    ("ignore-robots", OptionValue::Required),
+    ("download-covers", OptionValue::None),
+    ("block-on", OptionValue::Optional),
+    ("ignore-drift", OptionValue::None),
+    ("categories", OptionValue::Required),
+    ("exclude-categories", OptionValue::Required),
+    ("tags", OptionValue::Required),
+    ("exclude-tags", OptionValue::Required),
+    ("min-tag-count", OptionValue::Required),
+    ("sort", OptionValue::Required),
+    ("follow-related", OptionValue::None),
+    ("all-comments", OptionValue::None),
+    ("keywords", OptionValue::Required),
+    ("isbns", OptionValue::Required),
+    ("authors", OptionValue::Required),
+    ("fixtures", OptionValue::Required),
+];
+
+pub(crate) fn parse<I: Iterator<Item = String>>(args: I) -> anyhow::Result<Args> {
+    let mut res = Args::default();
+    // skip program name
+    for arg in args.skip(1) {
+        if let Some(option) = arg.strip_prefix("--") {
+            let mut kv = option.splitn(2, '=');
+            let name = kv.next().unwrap_or_default().trim();
+            if name.is_empty() {
+                return Err(anyhow!("invalid option, arg= {:?}", arg));
+            }
+
+            let option_value = match OPTIONS.iter().find(|(n, _)| *n == name) {
+                Some((_, v)) => *v,
+                None => return Err(anyhow!("unknown option, arg= {:?}", arg)),
+            };
+            let values: Vec<String> = kv
+                .next()
+                .unwrap_or_default()
+                .split(',')
+                .map(|v| v.trim())
+                .filter(|v| !v.is_empty())
+                .map(|v| v.to_owned())
+                .collect();
+            match option_value {
+                OptionValue::None if !values.is_empty() => {
+                    return Err(anyhow!("option takes no value, arg= {:?}", arg))
+                }
+                OptionValue::Required if values.is_empty() => {
+                    return Err(anyhow!("option needs a value, arg= {:?}", arg))
+                }
+                _ => (),
+            }
+            res.options
+                .entry(name.to_owned())
+                .or_default()
+                .extend(values);
+            continue;
+        }
+
+        if res.command.is_some() {
+            return Err(anyhow!("unexpected argument, arg= {:?}", arg));
+        }
+        if !COMMANDS.contains(&arg.as_str()) {
+            return Err(anyhow!("unknown command, arg= {:?}", arg));
+        }
+        res.command = Some(arg);
+    }
+
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_line(line: &str) -> anyhow::Result<Args> {
+        parse(
+            std::iter::once("rosario")
+                .chain(line.split(' '))
+                .map(|v| v.to_owned()),
+        )
+    }
+
+    #[test]
+    fn parse_command_and_options() {
+        let args = parse_line("retry-failed --ignore-robots=a.com,b.com --ignore-robots=c.com")
+            .expect("a valid command line");
+        assert_eq!(args.command.as_deref(), Some("retry-failed"));
+        assert_eq!(
+            args.values("ignore-robots"),
+            vec!["a.com", "b.com", "c.com"]
+        );
+        assert!(args.values("retry-failed").is_empty());
+        assert!(parse_line("--follow-related")
+            .expect("a flag")
+            .flag("follow-related"));
+        // blocks nothing
+        assert!(parse_line("--block-on")
+            .expect("optional value")
+            .flag("block-on"));
+
+        let args = parse_line("search --keywords=a.txt --isbns=b.txt")
+            .expect("a valid search command line");
+        assert_eq!(args.command.as_deref(), Some("search"));
+        assert_eq!(args.values("isbns"), vec!["b.txt"]);
+    }
+
+    #[test]
+    fn reject_unknown_and_incomplete_arguments() {
+        assert!(parse_line("retry-faild").is_err());
+        assert!(parse_line("--ignore-robot=book.douban.com").is_err());
+        assert!(parse_line("--ignore-robots").is_err());
+        assert!(parse_line("--sort=").is_err());
+        assert!(parse_line("--follow-related=yes").is_err());
+        assert!(parse_line("proxy retry-failed").is_err());
+    }
+}
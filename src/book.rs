@@ -1,25 +1,378 @@
+use std::collections::BTreeSet;
 use std::fmt;
 
+/// Whether douban shows a rating for the book, and whether we could read it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RatingState {
+    Rated,
+    /// "评价人数不足", rated by too few users for douban to show a score
+    Insufficient,
+    /// "目前无人评价"
+    NoRatings,
+    /// the rating block is missing or its numbers could not be read
+    #[default]
+    ParseFailed,
+}
+
+impl fmt::Display for RatingState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RatingState::Rated => write!(f, "rated"),
+            RatingState::Insufficient => write!(f, "insufficient"),
+            RatingState::NoRatings => write!(f, "no_ratings"),
+            RatingState::ParseFailed => write!(f, "parse_failed"),
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct Score {
-    pub score: f32,
-    pub score_num: i32,
-    pub five_star_pct: f32,
-    pub four_star_pct: f32,
-    pub three_star_pct: f32,
-    pub two_star_pct: f32,
-    pub one_star_pct: f32,
+    pub state: RatingState,
+    /// only set when rated, `None` is not a score of 0
+    pub score: Option<f32>,
+    pub score_num: Option<i32>,
+    pub five_star_pct: Option<f32>,
+    pub four_star_pct: Option<f32>,
+    pub three_star_pct: Option<f32>,
+    pub two_star_pct: Option<f32>,
+    pub one_star_pct: Option<f32>,
 }
 
 impl fmt::Display for Score {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        writeln!(f, "评分:\n豆瓣评分: {}", self.score)?;
-        writeln!(f, "评价人数: {}", self.score_num)?;
-        writeln!(f, "5星: {}", self.five_star_pct)?;
-        writeln!(f, "4星: {}", self.four_star_pct)?;
-        writeln!(f, "3星: {}", self.three_star_pct)?;
-        writeln!(f, "2星: {}", self.two_star_pct)?;
-        write!(f, "1星: {}", self.one_star_pct)
+        writeln!(f, "评分:\n评分状态: {}", self.state)?;
+        writeln!(f, "豆瓣评分: {}", option2string(&self.score))?;
+        writeln!(f, "评价人数: {}", option2string(&self.score_num))?;
+        writeln!(f, "5星: {}", option2string(&self.five_star_pct))?;
+        writeln!(f, "4星: {}", option2string(&self.four_star_pct))?;
+        writeln!(f, "3星: {}", option2string(&self.three_star_pct))?;
+        writeln!(f, "2星: {}", option2string(&self.two_star_pct))?;
+        write!(f, "1星: {}", option2string(&self.one_star_pct))
+    }
+}
+
+/// A date where the month and the day may be unknown, e.g. "2005-5".
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PartialDate {
+    pub year: i32,
+    pub month: Option<u32>,
+    pub day: Option<u32>,
+}
+
+impl fmt::Display for PartialDate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}", self.year)?;
+        if let Some(month) = self.month {
+            write!(f, "-{:02}", month)?;
+            if let Some(day) = self.day {
+                write!(f, "-{:02}", day)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Price {
+    pub amount: f64,
+    /// ISO 4217 code, e.g. "CNY"
+    pub currency: String,
+}
+
+impl fmt::Display for Price {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {:.2}", self.currency, self.amount)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContributorRole {
+    Author,
+    Translator,
+    /// 编者, 主编, 编著
+    Editor,
+    /// 绘者, 绘
+    Illustrator,
+}
+
+impl fmt::Display for ContributorRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContributorRole::Author => write!(f, "作者"),
+            ContributorRole::Translator => write!(f, "译者"),
+            ContributorRole::Editor => write!(f, "编者"),
+            ContributorRole::Illustrator => write!(f, "绘者"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Contributor {
+    /// display name, without nationality and role
+    pub name: String,
+    /// "美" of "[美] J. K. 罗琳", empty if not given
+    pub nationality: String,
+    pub role: ContributorRole,
+    /// id of a https://book.douban.com/author/{id} link
+    pub author_id: Option<String>,
+    /// id of a https://www.douban.com/personage/{id} link
+    pub personage_id: Option<String>,
+}
+
+impl fmt::Display for Contributor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.nationality.is_empty() {
+            write!(f, "[{}] ", self.nationality)?;
+        }
+        write!(f, "{} ({})", self.name, self.role)?;
+        if let Some(id) = &self.author_id {
+            write!(f, " <author:{}>", id)?;
+        }
+        if let Some(id) = &self.personage_id {
+            write!(f, " <personage:{}>", id)?;
+        }
+        Ok(())
+    }
+}
+
+/// A tag from the 豆瓣成员常用的标签 section.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tag {
+    pub name: String,
+    /// how many members used it, not shown on every page
+    pub count: Option<u32>,
+}
+
+impl fmt::Display for Tag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.count {
+            Some(count) => write!(f, "{}({})", self.name, count),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IsbnStatus {
+    #[default]
+    Missing,
+    Valid,
+    /// bad length, bad characters or bad checksum
+    Invalid,
+}
+
+impl fmt::Display for IsbnStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IsbnStatus::Missing => write!(f, "missing"),
+            IsbnStatus::Valid => write!(f, "valid"),
+            IsbnStatus::Invalid => write!(f, "invalid"),
+        }
+    }
+}
+
+/// Another douban subject the book page links to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubjectRef {
+    pub id: String,
+    pub title: String,
+}
+
+impl fmt::Display for SubjectRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} <subject:{}>", self.title, self.id)
+    }
+}
+
+/// A 短评, from the book page or the comments listing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Comment {
+    pub id: String,
+    pub user: String,
+    pub user_url: String,
+    /// stars, 1 to 5, not every comment rates the book
+    pub rating: Option<u8>,
+    pub date: Option<PartialDate>,
+    /// "有用" votes
+    pub votes: u32,
+    pub text: String,
+}
+
+impl fmt::Display for Comment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.user)?;
+        if let Some(rating) = self.rating {
+            write!(f, " ({}星)", rating)?;
+        }
+        write!(
+            f,
+            " {} 有用{}: {}",
+            option2string(&self.date),
+            self.votes,
+            self.text
+        )
+    }
+}
+
+/// The head of a 书评 as shown on the book page, the full review lives at `url`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReviewTeaser {
+    pub id: String,
+    pub title: String,
+    pub url: String,
+    pub author: String,
+    pub rating: Option<u8>,
+    pub teaser: String,
+}
+
+impl fmt::Display for ReviewTeaser {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} <review:{}> {}", self.title, self.id, self.author)?;
+        if let Some(rating) = self.rating {
+            write!(f, " ({}星)", rating)?;
+        }
+        write!(f, ": {}", self.teaser)
+    }
+}
+
+/// An entry of the 目录, with the entries under it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chapter {
+    pub title: String,
+    pub children: Vec<Chapter>,
+}
+
+/// Something wrong with the parsed book, named by the rule that found it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QualityIssue {
+    /// "missing.title", "suspicious.page_count", "unknown_label" ...
+    pub rule: String,
+    pub detail: String,
+}
+
+impl fmt::Display for QualityIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.detail.is_empty() {
+            write!(f, "{}", self.rule)
+        } else {
+            write!(f, "{} ({})", self.rule, self.detail)
+        }
+    }
+}
+
+/// How much of the page made it into the book.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QualityReport {
+    /// fields filled out of the fields checked
+    pub present: usize,
+    pub expected: usize,
+    pub issues: Vec<QualityIssue>,
+}
+
+impl fmt::Display for QualityReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}/{}, {}",
+            self.present,
+            self.expected,
+            vec2comma_seperated_string(self.issues.as_slice())
+        )
+    }
+}
+
+/// A downloaded cover, stored under its content hash.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoverImage {
+    /// relative path of the stored file, "covers/<sha256>.jpg"
+    pub path: String,
+    pub sha256: String,
+    pub mime_type: String,
+    pub width: usize,
+    pub height: usize,
+    pub size: usize,
+}
+
+impl fmt::Display for CoverImage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({}, {}x{}, {} bytes)",
+            self.path, self.mime_type, self.width, self.height, self.size
+        )
+    }
+}
+
+/// A tag of the root page, with the number of books douban shows next to it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagEntry {
+    pub name: String,
+    /// "/tag/小说", relative to the site
+    pub href: String,
+    pub count: Option<u32>,
+}
+
+impl fmt::Display for TagEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} ({})",
+            self.name,
+            self.href,
+            option2string(&self.count)
+        )
+    }
+}
+
+/// A heading of the root page, 文学, 流行, 文化 ..., and the tags under it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagCategory {
+    pub name: String,
+    pub tags: Vec<TagEntry>,
+}
+
+impl fmt::Display for TagCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:", self.name)?;
+        for tag in self.tags.iter() {
+            write!(f, "\n  {}", tag)?;
+        }
+        Ok(())
+    }
+}
+
+/// A book as listed on a tag page, enough for a catalog without fetching the book page.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ListingItem {
+    pub url: String,
+    /// subject id, parsed from `url`
+    pub id: String,
+    pub title: String,
+    /// "作者 / 译者 / 出版社 / 出版年 / 定价", as shown
+    pub pub_line: String,
+    pub rating_state: RatingState,
+    pub score: Option<f32>,
+    pub score_num: Option<u32>,
+    pub description: String,
+    /// position on the tag page, from 1
+    pub rank: usize,
+}
+
+impl fmt::Display for ListingItem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}. {} <subject:{}> {} | {} | {} {} ({}) | {}",
+            self.rank,
+            self.title,
+            self.id,
+            self.url,
+            self.pub_line,
+            self.rating_state,
+            option2string(&self.score),
+            option2string(&self.score_num),
+            self.description
+        )
     }
 }
 
@@ -29,22 +382,60 @@ pub struct Book {
     pub location: String,
     pub origin_title: String,
     pub subtitle: String,
-    pub author: Vec<String>,
-    pub translator: Vec<String>,
+    pub author: Vec<Contributor>,
+    pub translator: Vec<Contributor>,
     pub press: String,
+    pub press_id: Option<String>,
     pub producer: String,
+    pub producer_id: Option<String>,
     pub publication_year: String,
     pub page_num: String,
     pub price: String,
     pub binding: String,
     pub series: String,
+    pub series_id: Option<String>,
     pub isbn: String,
     pub unified_book_number: String,
     pub score: Score,
+    /// users who want to read, are reading and have read the book
+    pub wish_count: Option<u32>,
+    pub reading_count: Option<u32>,
+    pub read_count: Option<u32>,
+
+    // normalized from the raw text above
+    pub publication_date: Option<PartialDate>,
+    pub page_count: Option<u32>,
+    pub price_value: Option<Price>,
+    pub isbn13: Option<String>,
+    pub isbn10: Option<String>,
+    pub isbn_status: IsbnStatus,
 
+    pub tags: Vec<Tag>,
+    /// the tags whose listing the crawler found this book in
+    pub found_under_tags: BTreeSet<String>,
+    pub cover_url_small: String,
+    pub cover_url_large: String,
+    /// only set when covers are downloaded
+    pub cover: Option<CoverImage>,
+    /// "这本书的其他版本", the title is mostly the press name
+    pub other_editions: Vec<SubjectRef>,
+    /// other volumes of the same 丛书
+    pub series_volumes: Vec<SubjectRef>,
+    /// 喜欢读"X"的人也喜欢
+    pub also_liked: Vec<SubjectRef>,
+    /// "全部 N 条", the visible comments are only the first few
+    pub comment_count: Option<u32>,
+    pub comments: Vec<Comment>,
+    pub reviews: Vec<ReviewTeaser>,
+
+    /// paragraphs separated by an empty line, the full text when the page folds it
     pub content_intro: String,
     pub author_intro: String,
-    pub directory: String,
+    pub directory: Vec<Chapter>,
+
+    /// (label, value) of the basic info lines the parser does not know
+    pub unknown_info: Vec<(String, String)>,
+    pub quality: QualityReport,
 }
 
 impl fmt::Display for Book {
@@ -63,29 +454,124 @@ impl fmt::Display for Book {
             "译者: {}",
             vec2comma_seperated_string(self.translator.as_slice())
         )?;
-        writeln!(f, "出版社: {}", self.press)?;
-        writeln!(f, "出品方: {}", self.producer)?;
+        writeln!(
+            f,
+            "出版社: {}{}",
+            self.press,
+            id2string("press", &self.press_id)
+        )?;
+        writeln!(
+            f,
+            "出品方: {}{}",
+            self.producer,
+            id2string("producers", &self.producer_id)
+        )?;
         writeln!(f, "出版年: {}", self.publication_year)?;
         writeln!(f, "页数: {}", self.page_num)?;
         writeln!(f, "定价: {}", self.price)?;
         writeln!(f, "装帧: {}", self.binding)?;
-        writeln!(f, "丛书: {}", self.series)?;
+        writeln!(
+            f,
+            "丛书: {}{}",
+            self.series,
+            id2string("series", &self.series_id)
+        )?;
         writeln!(f, "isbn: {}", self.isbn)?;
         writeln!(f, "统一书号: {}", self.unified_book_number)?;
+        writeln!(f, "出版日期: {}", option2string(&self.publication_date))?;
+        writeln!(f, "页数(数值): {}", option2string(&self.page_count))?;
+        writeln!(f, "定价(数值): {}", option2string(&self.price_value))?;
+        writeln!(f, "ISBN-13: {}", option2string(&self.isbn13))?;
+        writeln!(f, "ISBN-10: {}", option2string(&self.isbn10))?;
+        writeln!(f, "ISBN状态: {}", self.isbn_status)?;
+        writeln!(
+            f,
+            "标签: {}",
+            vec2comma_seperated_string(self.tags.as_slice())
+        )?;
+        writeln!(
+            f,
+            "发现于标签: {}",
+            vec2comma_seperated_string(self.found_under_tags.iter().collect::<Vec<_>>().as_slice())
+        )?;
+        writeln!(f, "封面(小): {}", self.cover_url_small)?;
+        writeln!(f, "封面(大): {}", self.cover_url_large)?;
+        writeln!(f, "封面文件: {}", option2string(&self.cover))?;
+        writeln!(
+            f,
+            "其他版本: {}",
+            vec2comma_seperated_string(self.other_editions.as_slice())
+        )?;
+        writeln!(
+            f,
+            "丛书书目: {}",
+            vec2comma_seperated_string(self.series_volumes.as_slice())
+        )?;
+        writeln!(
+            f,
+            "喜欢读的人也喜欢: {}",
+            vec2comma_seperated_string(self.also_liked.as_slice())
+        )?;
+        writeln!(f, "想读: {}", option2string(&self.wish_count))?;
+        writeln!(f, "在读: {}", option2string(&self.reading_count))?;
+        writeln!(f, "读过: {}", option2string(&self.read_count))?;
+        writeln!(
+            f,
+            "未识别信息: {}",
+            self.unknown_info
+                .iter()
+                .map(|(label, value)| format!("{}: {}", label, value))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )?;
+        writeln!(f, "完整度: {}", self.quality)?;
         writeln!(f, "\n{}\n", self.score)?;
         writeln!(f, "内容简介:\n{}\n", self.content_intro)?;
         writeln!(f, "作者简介:\n{}\n", self.author_intro)?;
-        write!(f, "目录:\n{}", self.directory)
+        writeln!(f, "目录:")?;
+        write_chapters(f, self.directory.as_slice(), 0)?;
+        writeln!(f)?;
+        writeln!(f, "短评({}):", option2string(&self.comment_count))?;
+        for comment in self.comments.iter() {
+            writeln!(f, "{}", comment)?;
+        }
+        writeln!(f, "\n书评:")?;
+        for review in self.reviews.iter() {
+            writeln!(f, "{}", review)?;
+        }
+        Ok(())
     }
 }
 
-fn vec2comma_seperated_string(v: &[String]) -> String {
+fn write_chapters(f: &mut fmt::Formatter<'_>, chapters: &[Chapter], depth: usize) -> fmt::Result {
+    for chapter in chapters {
+        writeln!(f, "{}{}", "  ".repeat(depth), chapter.title)?;
+        write_chapters(f, chapter.children.as_slice(), depth + 1)?;
+    }
+    Ok(())
+}
+
+fn vec2comma_seperated_string<T: fmt::Display>(v: &[T]) -> String {
     let init_res = String::new();
     v.iter().fold(init_res, |mut res, s| {
         if !res.is_empty() {
             res.push_str(", ");
         }
-        res.push_str(s.as_str());
+        res.push_str(format!("{}", s).as_str());
         res
     })
 }
+
+fn id2string(kind: &str, id: &Option<String>) -> String {
+    match id {
+        Some(id) => format!(" <{}:{}>", kind, id),
+        None => String::new(),
+    }
+}
+
+fn option2string<T: fmt::Display>(v: &Option<T>) -> String {
+    match v {
+        Some(v) => format!("{}", v),
+        None => String::new(),
+    }
+}
@@ -0,0 +1,96 @@
+use lazy_static::lazy_static;
+use log::{debug, info, warn};
+use std::collections::HashSet;
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::path;
+use std::sync::{Mutex, RwLock};
+
+lazy_static! {
+    static ref DONE_TAG_PAGES: RwLock<HashSet<String>> = RwLock::new(HashSet::new());
+    static ref WRITER: Mutex<Option<BufWriter<fs::File>>> = Mutex::new(None);
+}
+
+// tag pages whose books are all processed, one url per line
+const CHECKPOINT_FILE: &str = "checkpoint";
+
+pub(crate) fn init() -> anyhow::Result<()> {
+    if path::Path::new(CHECKPOINT_FILE).is_file() {
+        let content = fs::read_to_string(CHECKPOINT_FILE)?;
+        let mut done_tag_pages = DONE_TAG_PAGES
+            .write()
+            .expect("failed to get DONE_TAG_PAGES write lock");
+        done_tag_pages.extend(
+            content
+                .lines()
+                .map(|l| l.trim())
+                .filter(|l| !l.is_empty())
+                .map(|l| l.to_owned()),
+        );
+        info!(
+            "checkpoint found, resume crawl, done tag page count= {:?}",
+            done_tag_pages.len()
+        );
+    }
+
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(CHECKPOINT_FILE)?;
+    *WRITER.lock().expect("failed to get checkpoint WRITER lock") = Some(BufWriter::new(file));
+
+    Ok(())
+}
+
+pub(crate) fn is_done(tag_page_url: &str) -> bool {
+    DONE_TAG_PAGES
+        .read()
+        .expect("failed to get DONE_TAG_PAGES read lock")
+        .contains(tag_page_url)
+}
+
+pub(crate) fn mark_done(tag_page_url: &str) {
+    DONE_TAG_PAGES
+        .write()
+        .expect("failed to get DONE_TAG_PAGES write lock")
+        .insert(tag_page_url.to_owned());
+
+    if let Some(writer) = WRITER
+        .lock()
+        .expect("failed to get checkpoint WRITER lock")
+        .as_mut()
+    {
+        // flushed right away, a second signal exits without unwinding
+        if let Err(e) = writeln!(writer, "{}", tag_page_url).and_then(|_| writer.flush()) {
+            warn!(
+                "failed to write checkpoint, e= {:?}, tag_page_url= {:?}",
+                e, tag_page_url
+            );
+        }
+    }
+}
+
+pub(crate) fn flush() {
+    if let Some(writer) = WRITER
+        .lock()
+        .expect("failed to get checkpoint WRITER lock")
+        .as_mut()
+    {
+        if let Err(e) = writer.flush() {
+            warn!("failed to flush checkpoint, e= {:?}", e);
+        }
+    }
+}
+
+/// Drops the checkpoint once the whole crawl is done, the next run starts over.
+pub(crate) fn clear() {
+    *WRITER.lock().expect("failed to get checkpoint WRITER lock") = None;
+    DONE_TAG_PAGES
+        .write()
+        .expect("failed to get DONE_TAG_PAGES write lock")
+        .clear();
+    match fs::remove_file(CHECKPOINT_FILE) {
+        Ok(()) => debug!("checkpoint cleared"),
+        Err(e) => warn!("failed to remove checkpoint, e= {:?}", e),
+    }
+}
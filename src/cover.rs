@@ -0,0 +1,116 @@
+use crate::book::{Book, CoverImage};
+use crate::error::{Error, FetchError, StoreError};
+use imagesize::ImageType;
+use log::{debug, info, warn};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static DOWNLOAD_ENABLED: AtomicBool = AtomicBool::new(false);
+
+// covers/<sha256>.<ext>, the same image is stored once however many books use it
+const COVER_TARGET_DIR: &str = "covers/";
+const TMP_FILE_PREFIX: &str = ".tmp_";
+
+/// Turns the cover download stage on, use --download-covers.
+pub(crate) fn init(download_enabled: bool) -> anyhow::Result<()> {
+    DOWNLOAD_ENABLED.store(download_enabled, Ordering::SeqCst);
+    if !download_enabled {
+        return Ok(());
+    }
+    info!("cover download enabled, dir= {:?}", COVER_TARGET_DIR);
+
+    fs::create_dir_all(COVER_TARGET_DIR)?;
+    for entry in fs::read_dir(COVER_TARGET_DIR)? {
+        let entry = match entry {
+            Ok(entry_) => entry_,
+            Err(e) => {
+                warn!("failed to get entry name, e= {:?}", e);
+                continue;
+            }
+        };
+
+        // left over by a write that did not finish
+        if entry
+            .file_name()
+            .to_string_lossy()
+            .starts_with(TMP_FILE_PREFIX)
+        {
+            debug!("remove unfinished cover file, path= {:?}", entry.path());
+            if let Err(e) = fs::remove_file(entry.path()) {
+                warn!("failed to remove unfinished cover file, e= {:?}", e);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub(crate) fn download_enabled() -> bool {
+    DOWNLOAD_ENABLED.load(Ordering::SeqCst)
+}
+
+fn mime_type(image_type: ImageType) -> (&'static str, &'static str) {
+    match image_type {
+        ImageType::Jpeg => ("image/jpeg", "jpg"),
+        ImageType::Png => ("image/png", "png"),
+        ImageType::Gif => ("image/gif", "gif"),
+        ImageType::Webp => ("image/webp", "webp"),
+        ImageType::Bmp => ("image/bmp", "bmp"),
+        ImageType::Avif => ("image/avif", "avif"),
+        _ => ("application/octet-stream", "bin"),
+    }
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Downloads the large cover of the book, or the small one when there is no large one.
+pub(crate) fn download(book: &Book) -> Result<Option<CoverImage>, Error> {
+    let url = if book.cover_url_large.is_empty() {
+        book.cover_url_small.as_str()
+    } else {
+        book.cover_url_large.as_str()
+    };
+    if url.is_empty() {
+        return Ok(None);
+    }
+
+    let data = crate::fetch::get_bytes(url, book.location.as_str())?;
+    let not_an_image = || FetchError::NotAnImage {
+        url: url.to_owned(),
+    };
+    let image_type = imagesize::image_type(data.as_slice()).map_err(|_| not_an_image())?;
+    let dimensions = imagesize::blob_size(data.as_slice()).map_err(|_| not_an_image())?;
+    let (mime_type, extension) = mime_type(image_type);
+
+    let sha256 = sha256_hex(data.as_slice());
+    let file_name = format!("{}.{}", sha256, extension);
+    let path = path::Path::new(COVER_TARGET_DIR).join(file_name.as_str());
+    if path.is_file() {
+        debug!("cover already stored, path= {:?}, url= {:?}", path, url);
+    } else {
+        let tmp_path =
+            path::Path::new(COVER_TARGET_DIR).join(format!("{}{}", TMP_FILE_PREFIX, file_name));
+        fs::write(&tmp_path, data.as_slice())
+            .and_then(|_| fs::rename(&tmp_path, &path))
+            .map_err(|e| StoreError::Io {
+                path: path.to_string_lossy().into_owned(),
+                source: e,
+            })?;
+    }
+
+    Ok(Some(CoverImage {
+        path: format!("{}{}", COVER_TARGET_DIR, file_name),
+        sha256,
+        mime_type: mime_type.to_owned(),
+        width: dimensions.width,
+        height: dimensions.height,
+        size: data.len(),
+    }))
+}
@@ -0,0 +1,762 @@
+use crate::book::{Book, ListingItem};
+use crate::dead_letter::PageKind;
+use crate::error::{Error, ErrorAction, ParseError};
+use crate::parser::{
+    book_page::get_and_parse_book_page,
+    comments_page::{comments_page_url, get_and_parse_comments_page},
+    root_page::{get_and_parse_root_page, ROOT_URL},
+    search_page::{
+        get_and_parse_search_page, search_page_url, COUNT_PER_SEARCH_PAGE, SEARCH_REFERRER,
+    },
+    tag_page::get_and_parse_tag_page,
+};
+use crate::taxonomy::ListingEnd;
+use lazy_static::lazy_static;
+use log::{debug, info, warn};
+use std::collections::{HashSet, VecDeque};
+use std::sync::RwLock;
+
+const TARGET_COUNT: usize = 10000;
+const HOST: &str = "https://book.douban.com";
+const COUNT_PER_PAGE: i32 = 20;
+const MAX_ATTEMPTS: usize = 3;
+
+lazy_static! {
+    static ref OPTIONS: RwLock<Options> = RwLock::new(Options::default());
+}
+
+/// The orders a tag page can list its books in, the `type` query parameter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SortOrder {
+    /// "综合排序", douban's default
+    Composite,
+    /// "按评价排序", top rated first
+    Rating,
+    /// "按出版日期排序", newest first
+    PublicationDate,
+}
+
+impl SortOrder {
+    /// "T", "S" or "R" as in the url, or the name of the order.
+    pub(crate) fn parse(name: &str) -> Option<SortOrder> {
+        match name {
+            "T" | "composite" => Some(SortOrder::Composite),
+            "S" | "rating" => Some(SortOrder::Rating),
+            "R" | "date" => Some(SortOrder::PublicationDate),
+            _ => None,
+        }
+    }
+
+    fn code(self) -> &'static str {
+        match self {
+            SortOrder::Composite => "T",
+            SortOrder::Rating => "S",
+            SortOrder::PublicationDate => "R",
+        }
+    }
+}
+
+/// Optional crawl stages, all off by default.
+#[derive(Debug, Clone)]
+pub(crate) struct Options {
+    /// also crawl the editions, series volumes and recommendations of every stored book,
+    /// they reach books no tag listing shows
+    pub(crate) follow_related: bool,
+    /// page through the full comments listing instead of keeping the few on the book page
+    pub(crate) all_comments: bool,
+    /// every tag is listed in each of these orders, a book listed in several is crawled once
+    pub(crate) sort_orders: Vec<SortOrder>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            follow_related: false,
+            all_comments: false,
+            sort_orders: vec![SortOrder::Composite],
+        }
+    }
+}
+
+pub(crate) fn init(options: Options) {
+    info!("crawler options: {:?}", options);
+    *OPTIONS.write().expect("failed to get OPTIONS write lock") = options;
+}
+
+fn options() -> Options {
+    OPTIONS
+        .read()
+        .expect("failed to get OPTIONS read lock")
+        .clone()
+}
+
+/// How a crawl ended, when it did not abort.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Outcome {
+    /// everything reachable has been processed
+    Finished,
+    TargetReached,
+    /// stopped by a signal
+    Interrupted,
+    /// stopped because the pages no longer parse like they used to, see `drift`
+    Paused,
+}
+
+/// The error that made the crawler give up, with how many times it tried.
+struct Failed {
+    error: Error,
+    attempts: usize,
+}
+
+/// Calls `f` again while it fails with a retryable error, at most `MAX_ATTEMPTS` times.
+fn with_retry<T, F>(url: &str, mut f: F) -> Result<T, Failed>
+where
+    F: FnMut() -> Result<T, Error>,
+{
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        let e = match f() {
+            Ok(v) => return Ok(v),
+            Err(e) => e,
+        };
+
+        if e.action() == ErrorAction::Retry && attempts < MAX_ATTEMPTS {
+            warn!(
+                "failed, retry, attempt= {:?}, e= {:?}, url= {:?}",
+                attempts, e, url
+            );
+            crate::stats::add_error(&e, ErrorAction::Retry);
+            continue;
+        }
+
+        return Err(Failed { error: e, attempts });
+    }
+}
+
+/// Records the failure, and only hands the error back if the crawl must stop.
+///
+/// Urls of skipped pages end up in the dead letter store, see `retry_failed`.
+fn give_up(failed: Failed, page_kind: PageKind, url: &str, referrer: &str) -> Result<(), Error> {
+    let e = failed.error;
+    let action = match e.action() {
+        // out of retries
+        ErrorAction::Retry | ErrorAction::Requeue => ErrorAction::Skip,
+        action => action,
+    };
+    crate::stats::add_error(&e, action);
+    if action == ErrorAction::Abort {
+        return Err(e);
+    }
+
+    warn!("give up, e= {:?}, url= {:?}", e, url);
+    crate::dead_letter::record(page_kind, url, referrer, &e, failed.attempts);
+    Ok(())
+}
+
+pub(crate) fn run() -> Result<Outcome, Error> {
+    // parse root page, the taxonomy of a previous run stands in for it when it fails
+    let categories = match with_retry(ROOT_URL, get_and_parse_root_page) {
+        Ok(categories) => {
+            info!("parse root page success");
+            if let Err(e) = crate::taxonomy::store(categories.as_slice()) {
+                warn!("failed to store taxonomy, e= {:?}", e);
+            }
+            categories
+        }
+        Err(failed) => match crate::taxonomy::load() {
+            Ok(categories) if !categories.is_empty() => {
+                warn!(
+                    "failed to parse root page, use the stored taxonomy, e= {:?}",
+                    failed.error
+                );
+                categories
+            }
+            _ => return Err(failed.error),
+        },
+    };
+    let tags = crate::taxonomy::selected(categories.as_slice());
+    info!(
+        "tags to crawl, count= {:?}, category count= {:?}",
+        tags.len(),
+        categories.len()
+    );
+    debug!("tags= {:?}", tags);
+
+    let current_count: usize = crate::store::current_store_count();
+    info!("current store book count is {:?}", current_count);
+
+    for tag in tags {
+        // percent encoded like the "后页" links, so a link back to the first page is seen as one
+        let tag_url = crate::utils::absolute_url(ROOT_URL, tag.href.as_str())
+            .unwrap_or_else(|| format!("{}{}", HOST, tag.href));
+        match crawl_tag(tag_url.as_str())? {
+            Outcome::Finished => (),
+            outcome => return Ok(outcome),
+        }
+    }
+
+    // the next run starts from the beginning
+    crate::checkpoint::clear();
+    Ok(Outcome::Finished)
+}
+
+fn crawl_tag(tag_url: &str) -> Result<Outcome, Error> {
+    // books already listed under this tag in another order
+    let mut listed: HashSet<String> = HashSet::new();
+    for sort_order in options().sort_orders {
+        match crawl_tag_pages(tag_url, sort_order, &mut listed)? {
+            Outcome::Finished => (),
+            outcome => return Ok(outcome),
+        }
+    }
+
+    Ok(Outcome::Finished)
+}
+
+fn sorted_tag_page_url(tag_url: &str, sort_order: SortOrder, idx: i32) -> String {
+    format!(
+        "{}?start={}&type={}",
+        tag_url,
+        idx * COUNT_PER_PAGE,
+        sort_order.code()
+    )
+}
+
+// far past the depth douban lists, only guards against "后页" links that never end
+const MAX_TAG_PAGES: usize = 200;
+
+/// Follows the "后页" links of a tag in one order, `listed` is shared by the orders of the tag.
+fn crawl_tag_pages(
+    tag_url: &str,
+    sort_order: SortOrder,
+    listed: &mut HashSet<String>,
+) -> Result<Outcome, Error> {
+    let listing_url = sorted_tag_page_url(tag_url, sort_order, 0);
+    // the checkpoint has tag page urls, and this for listings followed to their end
+    let listing_done = format!("{}#end", listing_url);
+    if crate::checkpoint::is_done(listing_done.as_str()) {
+        debug!("tag listing done in previous run, url= {:?}", listing_url);
+        return Ok(Outcome::Finished);
+    }
+
+    let mut tag_page_url = listing_url.clone();
+    let mut referrer = tag_url.to_owned();
+    // the offset of the page, the way on when a page has not been read in this run
+    let mut idx = 0;
+    let mut visited: HashSet<String> = HashSet::new();
+    // books listed in this order, a page listing none but these is a repeat
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut reach = crate::taxonomy::Reach::default();
+    // pages done in a previous run are not fetched again, their books are not counted
+    let mut reach_complete = true;
+    let end = loop {
+        if crate::shutdown::requested() {
+            return Ok(Outcome::Interrupted);
+        }
+        if MAX_TAG_PAGES <= visited.len() {
+            warn!(
+                "too many tag pages, stop following, url= {:?}",
+                tag_page_url
+            );
+            break ListingEnd::PageLimit;
+        }
+        if !visited.insert(tag_page_url.clone()) {
+            info!(
+                "next link leads back to a tag page visited before, stop following, url= {:?}",
+                tag_page_url
+            );
+            break ListingEnd::RepeatedPage;
+        }
+        let offset_url = sorted_tag_page_url(tag_url, sort_order, idx + 1);
+
+        if crate::checkpoint::is_done(tag_page_url.as_str()) {
+            debug!("tag page done in previous run, url= {:?}", tag_page_url);
+            reach_complete = false;
+            referrer = std::mem::replace(&mut tag_page_url, offset_url);
+            idx += 1;
+            continue;
+        }
+
+        // parse tag page, get book urls
+        let tag_page = match with_retry(tag_page_url.as_str(), || {
+            get_and_parse_tag_page(tag_page_url.as_str(), referrer.as_str())
+        }) {
+            Ok(tag_page) => tag_page,
+            Err(failed) => {
+                give_up(
+                    failed,
+                    PageKind::Tag,
+                    tag_page_url.as_str(),
+                    referrer.as_str(),
+                )?;
+                // without the page there is no "后页" link, go on only where the paginator said
+                match reach.page_count {
+                    Some(page_count) if idx + 1 < page_count => {
+                        reach_complete = false;
+                        referrer = std::mem::replace(&mut tag_page_url, offset_url);
+                        idx += 1;
+                        continue;
+                    }
+                    _ => break ListingEnd::FetchFailed,
+                }
+            }
+        };
+        info!("parse tag page suceess, url= {:?}", tag_page_url);
+        reach.pages += 1;
+        if reach.page_count.is_none() {
+            reach.page_count = tag_page.page_count;
+        }
+
+        if tag_page.items.is_empty() {
+            info!("tag page is empty, stop following, url= {:?}", tag_page_url);
+            crate::checkpoint::mark_done(tag_page_url.as_str());
+            break ListingEnd::EmptyPage;
+        }
+        let new_urls: Vec<String> = record_listing(&tag_page.items, tag_page_url.as_str())?
+            .into_iter()
+            .filter(|url| seen.insert(url.clone()))
+            .collect();
+        if new_urls.is_empty() {
+            info!(
+                "tag page repeats the pages before, stop following, url= {:?}",
+                tag_page_url
+            );
+            crate::checkpoint::mark_done(tag_page_url.as_str());
+            break ListingEnd::RepeatedPage;
+        }
+        reach.books += new_urls.len();
+
+        let books_url: Vec<String> = new_urls
+            .into_iter()
+            .filter(|url| listed.insert(url.clone()))
+            .collect();
+        match crawl_books(books_url, tag_page_url.as_str())? {
+            Outcome::Finished => crate::checkpoint::mark_done(tag_page_url.as_str()),
+            outcome => return Ok(outcome),
+        }
+
+        info!(
+            "store all books in this tag page success, tag_page_url= {:?}",
+            tag_page_url
+        );
+
+        match tag_page.next_url {
+            Some(next_url) => {
+                referrer = std::mem::replace(&mut tag_page_url, next_url);
+                idx += 1;
+            }
+            None => break ListingEnd::LastPage,
+        }
+    };
+
+    info!(
+        "tag listing end, end= {:?}, reach= {:?}, complete= {:?}, url= {:?}",
+        end, reach, reach_complete, listing_url
+    );
+    if reach_complete {
+        crate::taxonomy::record_reach(listing_url.as_str(), reach, end);
+    }
+    // a listing cut short is followed again on resume, its failed page from the start
+    match end {
+        ListingEnd::LastPage | ListingEnd::EmptyPage | ListingEnd::RepeatedPage => {
+            crate::checkpoint::mark_done(listing_done.as_str())
+        }
+        ListingEnd::FetchFailed | ListingEnd::PageLimit => (),
+    }
+
+    Ok(Outcome::Finished)
+}
+
+/// Crawls the books the search queries find, use cargo run search.
+///
+/// A tag listing stops after the first few hundred books, searches reach the rest of the catalogue.
+pub(crate) fn search(queries: Vec<crate::search::Query>) -> Result<Outcome, Error> {
+    info!("search queries, count= {:?}", queries.len());
+    // books found by an earlier query
+    let mut listed: HashSet<String> = HashSet::new();
+    for query in queries {
+        match search_query(&query, &mut listed)? {
+            Outcome::Finished => (),
+            outcome => return Ok(outcome),
+        }
+    }
+
+    Ok(Outcome::Finished)
+}
+
+fn search_query(
+    query: &crate::search::Query,
+    listed: &mut HashSet<String>,
+) -> Result<Outcome, Error> {
+    let mut total = None;
+    for idx in 0..query.max_pages() {
+        if crate::shutdown::requested() {
+            return Ok(Outcome::Interrupted);
+        }
+        let start = idx * COUNT_PER_SEARCH_PAGE;
+        if matches!(total, Some(total) if total <= start) {
+            break;
+        }
+
+        let search_page_url = search_page_url(query.text.as_str(), start);
+        if crate::checkpoint::is_done(search_page_url.as_str()) {
+            debug!(
+                "search page done in previous run, url= {:?}",
+                search_page_url
+            );
+            continue;
+        }
+
+        let search_page = match with_retry(search_page_url.as_str(), || {
+            get_and_parse_search_page(search_page_url.as_str(), SEARCH_REFERRER)
+        }) {
+            Ok(search_page) => search_page,
+            Err(failed) => {
+                give_up(
+                    failed,
+                    PageKind::Search,
+                    search_page_url.as_str(),
+                    SEARCH_REFERRER,
+                )?;
+                // the next pages are only known to exist from the total, the first page
+                // is read again for it when it was done in a previous run
+                if total.is_none() && 0 < idx {
+                    total = search_total(query);
+                }
+                if total.is_some() {
+                    continue;
+                }
+                break;
+            }
+        };
+        info!(
+            "parse search page success, total= {:?}, url= {:?}",
+            search_page.total, search_page_url
+        );
+        if search_page.items.is_empty() {
+            crate::checkpoint::mark_done(search_page_url.as_str());
+            break;
+        }
+        total = search_page.total.or(total);
+
+        let kept: Vec<ListingItem> = search_page
+            .items
+            .into_iter()
+            .filter(|item| query.keeps(item))
+            .collect();
+        let books_url: Vec<String> = record_listing(&kept, search_page_url.as_str())?
+            .into_iter()
+            .filter(|url| listed.insert(url.clone()))
+            .collect();
+        match crawl_books(books_url, search_page_url.as_str())? {
+            Outcome::Finished => crate::checkpoint::mark_done(search_page_url.as_str()),
+            outcome => return Ok(outcome),
+        }
+    }
+
+    Ok(Outcome::Finished)
+}
+
+/// The result count of the query, from its first page.
+fn search_total(query: &crate::search::Query) -> Option<u32> {
+    let first_page_url = search_page_url(query.text.as_str(), 0);
+    match with_retry(first_page_url.as_str(), || {
+        get_and_parse_search_page(first_page_url.as_str(), SEARCH_REFERRER)
+    }) {
+        Ok(search_page) => search_page.total,
+        Err(failed) => {
+            warn!(
+                "failed to get search total, e= {:?}, url= {:?}",
+                failed.error, first_page_url
+            );
+            None
+        }
+    }
+}
+
+/// Keeps the listed books in the catalog, returns their urls in listing order.
+fn record_listing(listing_items: &[ListingItem], listing_url: &str) -> Result<Vec<String>, Error> {
+    if let Err(e) = crate::store::add_listing_items(listing_url, listing_items) {
+        let e = Error::from(e);
+        if e.action() == ErrorAction::Abort {
+            return Err(e);
+        }
+        warn!(
+            "failed to add listing items, e= {:?}, url= {:?}",
+            e, listing_url
+        );
+        crate::stats::add_error(&e, ErrorAction::Skip);
+    }
+
+    Ok(listing_items.iter().map(|item| item.url.clone()).collect())
+}
+
+fn crawl_books(books_url: Vec<String>, tag_page_url: &str) -> Result<Outcome, Error> {
+    // (book_url, referrer, attempts before requeued)
+    let mut queue: VecDeque<(String, String, Option<usize>)> = books_url
+        .into_iter()
+        .map(|url| (url, tag_page_url.to_owned(), None))
+        .collect();
+    let mut queued: HashSet<String> = queue.iter().map(|(url, _, _)| url.clone()).collect();
+    while let Some((book_url, referrer, requeued)) = queue.pop_front() {
+        if crate::shutdown::requested() {
+            return Ok(Outcome::Interrupted);
+        }
+        if crate::drift::paused() {
+            return Ok(Outcome::Paused);
+        }
+
+        let current_count = crate::store::current_store_count();
+        if TARGET_COUNT <= current_count {
+            info!(
+                "reach target count, current count= {:?}, target count= {:?}, stop process.",
+                current_count, TARGET_COUNT
+            );
+            return Ok(Outcome::TargetReached);
+        }
+
+        // kept for stored books too, a book shows up under many tags
+        if let Some(tag) = crate::utils::parse_tag_name(referrer.as_str()) {
+            if let Err(e) = crate::store::add_book_tag(book_url.as_str(), tag.as_str()) {
+                let e = Error::from(e);
+                if e.action() == ErrorAction::Abort {
+                    return Err(e);
+                }
+                warn!("failed to add book tag, e= {:?}, url= {:?}", e, book_url);
+                crate::stats::add_error(&e, ErrorAction::Skip);
+            }
+        }
+
+        if crate::store::is_already_store(book_url.as_str()) {
+            info!("book has been stored, url= {:?}", book_url);
+            crate::stats::add_book_already_stored();
+            continue;
+        }
+
+        match with_retry(book_url.as_str(), || {
+            crawl_book(book_url.as_str(), referrer.as_str())
+        }) {
+            Ok(related_urls) => {
+                crate::dead_letter::remove(book_url.as_str());
+                for url in related_urls {
+                    if crate::store::is_already_store(url.as_str()) || !queued.insert(url.clone()) {
+                        continue;
+                    }
+                    debug!("follow related book, url= {:?}, from= {:?}", url, book_url);
+                    queue.push_back((url, book_url.clone(), None));
+                }
+            }
+            // put it to the end of this page once, the next try goes through another proxy
+            Err(failed) if failed.error.action() == ErrorAction::Requeue && requeued.is_none() => {
+                warn!("requeue book, e= {:?}, url= {:?}", failed.error, book_url);
+                crate::stats::add_error(&failed.error, ErrorAction::Requeue);
+                queue.push_back((book_url, referrer, Some(failed.attempts)));
+            }
+            Err(mut failed) => {
+                failed.attempts += requeued.unwrap_or_default();
+                give_up(failed, PageKind::Book, book_url.as_str(), referrer.as_str())?
+            }
+        }
+    }
+
+    Ok(Outcome::Finished)
+}
+
+/// Stores the book, returns the related book urls to crawl next when following them.
+fn crawl_book(book_url: &str, referrer: &str) -> Result<Vec<String>, Error> {
+    let mut book = get_and_parse_book_page(book_url, referrer)?;
+    book.found_under_tags = crate::store::book_tags(book_url);
+    book.quality = crate::validate::check(&book);
+    crate::stats::add_quality_issues(&book.quality);
+    crate::drift::observe(book_url, &book);
+    let blocking_issues = crate::validate::blocking_issues(&book.quality);
+    if !blocking_issues.is_empty() {
+        return Err(ParseError::Incomplete {
+            url: book_url.to_owned(),
+            issues: blocking_issues.iter().map(|i| i.to_string()).collect(),
+        }
+        .into());
+    }
+    let book_title = book.title.clone();
+    info!(
+        "parse book success, title= {:?}, rating_state= {:?}, url= {:?}",
+        book_title, book.score.state, book_url
+    );
+    // douban sometimes has several subjects for one isbn, keep them all
+    if let Some(isbn13) = book.isbn13.as_ref() {
+        let book_id = crate::utils::parse_book_id(book_url);
+        let other_book_ids: Vec<String> = crate::store::find_by_isbn(isbn13)
+            .into_iter()
+            .filter(|id| *id != book_id)
+            .collect();
+        if !other_book_ids.is_empty() {
+            warn!(
+                "isbn already stored with other books, isbn= {:?}, book_ids= {:?}, url= {:?}",
+                isbn13, other_book_ids, book_url
+            );
+        }
+    }
+
+    if crate::cover::download_enabled() {
+        download_cover(&mut book)?;
+    }
+
+    let options = options();
+    if options.all_comments {
+        crawl_comments(&mut book)?;
+    }
+
+    let related_urls = if options.follow_related {
+        related_book_urls(&book)
+    } else {
+        Vec::new()
+    };
+
+    crate::store::store(book_url, book)?;
+    info!(
+        "store book success, title= {:?}, url= {:?}",
+        book_title, book_url
+    );
+    crate::stats::add_book_stored();
+
+    Ok(related_urls)
+}
+
+fn related_book_urls(book: &Book) -> Vec<String> {
+    book.other_editions
+        .iter()
+        .chain(book.series_volumes.iter())
+        .chain(book.also_liked.iter())
+        .map(|subject| format!("{}/subject/{}/", HOST, subject.id))
+        .collect()
+}
+
+/// Gives up on a stage the book does not depend on, only an abort stops the book.
+fn give_up_stage(failed: Failed, stage: &str, book_url: &str) -> Result<(), Error> {
+    let action = match failed.error.action() {
+        ErrorAction::Abort => ErrorAction::Abort,
+        _ => ErrorAction::Skip,
+    };
+    crate::stats::add_error(&failed.error, action);
+    if action == ErrorAction::Abort {
+        return Err(failed.error);
+    }
+
+    warn!(
+        "{} failed, keep the book without it, e= {:?}, url= {:?}",
+        stage, failed.error, book_url
+    );
+    Ok(())
+}
+
+/// A cover that can not be downloaded does not cost the book, it is stored without one.
+fn download_cover(book: &mut Book) -> Result<(), Error> {
+    match with_retry(book.cover_url_large.as_str(), || {
+        crate::cover::download(book)
+    }) {
+        Ok(cover) => book.cover = cover,
+        Err(failed) => give_up_stage(failed, "download cover", book.location.as_str())?,
+    }
+
+    Ok(())
+}
+
+// douban stops serving the listing somewhere after this for anonymous clients anyway
+const MAX_COMMENT_PAGES: usize = 50;
+
+/// Replaces the comments shown on the book page by the full listing, as far as it goes.
+fn crawl_comments(book: &mut Book) -> Result<(), Error> {
+    let mut seen: HashSet<String> = book.comments.iter().map(|c| c.id.clone()).collect();
+    let mut referrer = book.location.clone();
+    for page_idx in 0..MAX_COMMENT_PAGES {
+        if crate::shutdown::requested() {
+            break;
+        }
+
+        let url = comments_page_url(book.location.as_str(), page_idx);
+        let comments = match with_retry(url.as_str(), || {
+            get_and_parse_comments_page(url.as_str(), referrer.as_str())
+        }) {
+            Ok(v) => v,
+            // what has been collected so far is kept
+            Err(failed) => return give_up_stage(failed, "crawl comments", url.as_str()),
+        };
+
+        let mut new_count = 0;
+        for comment in comments {
+            // a comment without an id can not be told apart from one already kept
+            if !comment.id.is_empty() && seen.insert(comment.id.clone()) {
+                book.comments.push(comment);
+                new_count += 1;
+            }
+        }
+        debug!(
+            "comments page parsed, new_count= {:?}, url= {:?}",
+            new_count, url
+        );
+        // past the last page douban serves an empty one, or the last one again
+        if new_count == 0 {
+            break;
+        }
+        referrer = url;
+    }
+
+    Ok(())
+}
+
+/// Processes the urls in the dead letter store again, the ones that succeed are removed from it.
+pub(crate) fn retry_failed() -> Result<Outcome, Error> {
+    let dead_letters = crate::dead_letter::all();
+    info!("retry failed urls, count= {:?}", dead_letters.len());
+    for dead_letter in dead_letters {
+        if crate::shutdown::requested() {
+            return Ok(Outcome::Interrupted);
+        }
+
+        let url = dead_letter.url.as_str();
+        let referrer = dead_letter.referrer.as_str();
+        info!(
+            "retry failed url, page_kind= {:?}, error_kind= {:?}, attempts= {:?}, url= {:?}",
+            dead_letter.page_kind, dead_letter.error_kind, dead_letter.attempts, url
+        );
+        match dead_letter.page_kind {
+            PageKind::Tag => match with_retry(url, || get_and_parse_tag_page(url, referrer)) {
+                Ok(tag_page) => {
+                    crate::dead_letter::remove(url);
+                    match crawl_books(record_listing(&tag_page.items, url)?, url)? {
+                        // the next run skips it like the pages crawled the first time
+                        Outcome::Finished => crate::checkpoint::mark_done(url),
+                        outcome => return Ok(outcome),
+                    }
+                }
+                Err(failed) => give_up(failed, PageKind::Tag, url, referrer)?,
+            },
+            // the query is not kept, every book of the page is crawled
+            PageKind::Search => {
+                match with_retry(url, || get_and_parse_search_page(url, referrer)) {
+                    Ok(search_page) => {
+                        crate::dead_letter::remove(url);
+                        match crawl_books(record_listing(&search_page.items, url)?, url)? {
+                            Outcome::Finished => (),
+                            outcome => return Ok(outcome),
+                        }
+                    }
+                    Err(failed) => give_up(failed, PageKind::Search, url, referrer)?,
+                }
+            }
+            PageKind::Book => {
+                if crate::store::is_already_store(url) {
+                    crate::dead_letter::remove(url);
+                    continue;
+                }
+                match crawl_books(vec![url.to_owned()], referrer)? {
+                    Outcome::Finished => (),
+                    outcome => return Ok(outcome),
+                }
+            }
+        }
+    }
+
+    Ok(Outcome::Finished)
+}
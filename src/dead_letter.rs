@@ -0,0 +1,310 @@
+use crate::error::Error;
+use lazy_static::lazy_static;
+use log::{debug, warn};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path;
+use std::sync::RwLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+lazy_static! {
+    static ref DEAD_LETTERS: RwLock<BTreeMap<String, DeadLetter>> = RwLock::new(BTreeMap::new());
+}
+
+const DEAD_LETTER_FILE: &str = "dead_letter";
+const DEAD_LETTER_FILE_TMP: &str = "dead_letter.tmp";
+const FIELD_SEPARATOR: char = '\t';
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum PageKind {
+    Tag,
+    Book,
+    Search,
+}
+
+impl PageKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            PageKind::Tag => "tag",
+            PageKind::Book => "book",
+            PageKind::Search => "search",
+        }
+    }
+
+    fn from_str(s: &str) -> Option<PageKind> {
+        match s {
+            "tag" => Some(PageKind::Tag),
+            "book" => Some(PageKind::Book),
+            "search" => Some(PageKind::Search),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct DeadLetter {
+    pub(crate) page_kind: PageKind,
+    pub(crate) url: String,
+    pub(crate) referrer: String,
+    pub(crate) error_kind: String,
+    pub(crate) attempts: usize,
+    // unix timestamp, in seconds
+    pub(crate) first_failed: u64,
+    pub(crate) last_failed: u64,
+    pub(crate) message: String,
+}
+
+impl fmt::Display for DeadLetter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sep = FIELD_SEPARATOR;
+        write!(
+            f,
+            "{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}{sep}{}",
+            self.page_kind.as_str(),
+            self.url,
+            self.referrer,
+            self.error_kind,
+            self.attempts,
+            self.first_failed,
+            self.last_failed,
+            self.message,
+            sep = sep
+        )
+    }
+}
+
+fn parse_line(line: &str) -> Option<DeadLetter> {
+    let blocks: Vec<_> = line.split(FIELD_SEPARATOR).collect();
+    if blocks.len() != 8 {
+        return None;
+    }
+
+    Some(DeadLetter {
+        page_kind: PageKind::from_str(blocks[0])?,
+        url: blocks[1].to_owned(),
+        referrer: blocks[2].to_owned(),
+        error_kind: blocks[3].to_owned(),
+        attempts: blocks[4].parse().ok()?,
+        first_failed: blocks[5].parse().ok()?,
+        last_failed: blocks[6].parse().ok()?,
+        message: blocks[7].to_owned(),
+    })
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+fn clean_field(s: &str) -> String {
+    s.replace(&[FIELD_SEPARATOR, '\n', '\r'][..], " ")
+}
+
+pub(crate) fn init() -> anyhow::Result<()> {
+    if !path::Path::new(DEAD_LETTER_FILE).is_file() {
+        debug!("no dead letter file found");
+        return Ok(());
+    }
+
+    let content = fs::read_to_string(DEAD_LETTER_FILE)?;
+    let mut dead_letters = DEAD_LETTERS
+        .write()
+        .expect("failed to get DEAD_LETTERS write lock");
+    *dead_letters = parse_content(content.as_str());
+    debug!("load dead letters success, count= {:?}", dead_letters.len());
+
+    Ok(())
+}
+
+fn parse_content(content: &str) -> BTreeMap<String, DeadLetter> {
+    let mut dead_letters = BTreeMap::new();
+    for line in content.lines() {
+        match parse_line(line) {
+            Some(dead_letter) => {
+                dead_letters.insert(dead_letter.url.clone(), dead_letter);
+            }
+            None => warn!("load dead letter error, bad line, line= {:?}", line),
+        }
+    }
+    dead_letters
+}
+
+fn content(dead_letters: &BTreeMap<String, DeadLetter>) -> String {
+    dead_letters.values().fold(String::new(), |mut content, v| {
+        content.push_str(format!("{}\n", v).as_str());
+        content
+    })
+}
+
+fn flush(dead_letters: &BTreeMap<String, DeadLetter>) -> io::Result<()> {
+    fs::write(DEAD_LETTER_FILE_TMP, content(dead_letters))?;
+    fs::rename(DEAD_LETTER_FILE_TMP, DEAD_LETTER_FILE)
+}
+
+/// Records a url the crawler gave up on, or updates the existing record of it.
+pub(crate) fn record(page_kind: PageKind, url: &str, referrer: &str, e: &Error, attempts: usize) {
+    let mut dead_letters = DEAD_LETTERS
+        .write()
+        .expect("failed to get DEAD_LETTERS write lock");
+    add(
+        &mut dead_letters,
+        page_kind,
+        url,
+        referrer,
+        e,
+        attempts,
+        now(),
+    );
+
+    if let Err(e) = flush(&dead_letters) {
+        warn!("failed to flush dead letters, e= {:?}, url= {:?}", e, url);
+    }
+}
+
+fn add(
+    dead_letters: &mut BTreeMap<String, DeadLetter>,
+    page_kind: PageKind,
+    url: &str,
+    referrer: &str,
+    e: &Error,
+    attempts: usize,
+    now: u64,
+) {
+    let dead_letter = dead_letters
+        .entry(url.to_owned())
+        .or_insert_with(|| DeadLetter {
+            page_kind,
+            url: clean_field(url),
+            referrer: String::new(),
+            error_kind: String::new(),
+            attempts: 0,
+            first_failed: now,
+            last_failed: now,
+            message: String::new(),
+        });
+    dead_letter.referrer = clean_field(referrer);
+    dead_letter.error_kind = e.kind().to_owned();
+    dead_letter.attempts += attempts;
+    dead_letter.last_failed = now;
+    dead_letter.message = clean_field(format!("{}", e).as_str());
+}
+
+/// Removes the url once it has been processed successfully.
+pub(crate) fn remove(url: &str) {
+    let mut dead_letters = DEAD_LETTERS
+        .write()
+        .expect("failed to get DEAD_LETTERS write lock");
+    if dead_letters.remove(url).is_none() {
+        return;
+    }
+
+    if let Err(e) = flush(&dead_letters) {
+        warn!("failed to flush dead letters, e= {:?}, url= {:?}", e, url);
+    }
+}
+
+pub(crate) fn all() -> Vec<DeadLetter> {
+    DEAD_LETTERS
+        .read()
+        .expect("failed to get DEAD_LETTERS read lock")
+        .values()
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::FetchError;
+
+    const URL: &str = "https://book.douban.com/subject/1003000/";
+
+    fn unavailable() -> Error {
+        FetchError::Status {
+            url: URL.to_owned(),
+            status: 503,
+        }
+        .into()
+    }
+
+    #[test]
+    fn failures_of_one_url_add_up() {
+        let mut dead_letters = BTreeMap::new();
+        add(
+            &mut dead_letters,
+            PageKind::Book,
+            URL,
+            "a",
+            &unavailable(),
+            3,
+            100,
+        );
+        let not_found: Error = FetchError::NotFound {
+            url: URL.to_owned(),
+        }
+        .into();
+        add(
+            &mut dead_letters,
+            PageKind::Book,
+            URL,
+            "b",
+            &not_found,
+            1,
+            200,
+        );
+
+        assert_eq!(dead_letters.len(), 1);
+        let dead_letter = &dead_letters[URL];
+        assert_eq!(dead_letter.attempts, 4);
+        assert_eq!(
+            (dead_letter.first_failed, dead_letter.last_failed),
+            (100, 200)
+        );
+        // the last failure tells what to look at
+        assert_eq!(dead_letter.referrer, "b");
+        assert_eq!(dead_letter.error_kind, "fetch.not_found");
+    }
+
+    #[test]
+    fn reload_what_was_flushed() {
+        let mut dead_letters = BTreeMap::new();
+        add(
+            &mut dead_letters,
+            PageKind::Tag,
+            "https://book.douban.com/tag/小说?start=20&type=T",
+            "https://book.douban.com/tag/",
+            &unavailable(),
+            3,
+            100,
+        );
+        // separators in the message would split the line
+        add(
+            &mut dead_letters,
+            PageKind::Book,
+            URL,
+            "a\tb",
+            &unavailable(),
+            3,
+            100,
+        );
+
+        let reloaded = parse_content(content(&dead_letters).as_str());
+        assert_eq!(reloaded.len(), 2);
+        let dead_letter = &reloaded[URL];
+        assert_eq!(dead_letter.page_kind, PageKind::Book);
+        assert_eq!(dead_letter.referrer, "a b");
+        assert_eq!(dead_letter.attempts, 3);
+        assert_eq!(dead_letter.message, format!("{}", unavailable()));
+
+        // a url that succeeds on retry is gone from the file
+        dead_letters.remove(URL);
+        let reloaded = parse_content(content(&dead_letters).as_str());
+        assert_eq!(reloaded.len(), 1);
+        assert!(!reloaded.contains_key(URL));
+        assert!(parse_content("book\tnot enough fields\n").is_empty());
+    }
+}
@@ -0,0 +1,296 @@
+use crate::book::Book;
+use lazy_static::lazy_static;
+use log::{error, info, warn};
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+
+lazy_static! {
+    static ref DETECTOR: RwLock<Detector> = RwLock::new(Detector::default());
+}
+
+static PAUSE_ON_DRIFT: AtomicBool = AtomicBool::new(true);
+static PAUSED: AtomicBool = AtomicBool::new(false);
+
+// the books the current rates are taken over, and the least the baseline is taken over
+const WINDOW_SIZE: usize = 50;
+// a field filled less often than this before is too unreliable to watch
+const MIN_BASELINE_RATE: f32 = 0.5;
+// the window rate at or below this share of the baseline rate counts as a sharp drop
+const MAX_DROP_RATIO: f32 = 0.5;
+// a label on a single book is an odd book, on several a new markup
+const MIN_NEW_LABEL_BOOKS: usize = 3;
+const MAX_SAMPLE_URLS: usize = 5;
+
+/// Turns pausing off, the drift is only logged, use --ignore-drift.
+pub(crate) fn init(pause_on_drift: bool) {
+    if !pause_on_drift {
+        info!("markup drift is logged only, the crawl is not paused");
+    }
+    PAUSE_ON_DRIFT.store(pause_on_drift, Ordering::SeqCst);
+}
+
+/// Whether the crawl should stop after the current item because the markup changed.
+pub(crate) fn paused() -> bool {
+    PAUSED.load(Ordering::SeqCst)
+}
+
+/// Adds the parsed book to the window, and pauses the crawl on drift.
+pub(crate) fn observe(book_url: &str, book: &Book) {
+    let observation = Observation {
+        url: book_url.to_owned(),
+        fields: crate::validate::field_presence(book),
+        unknown_labels: book
+            .unknown_info
+            .iter()
+            .map(|(label, _)| label.clone())
+            .collect(),
+    };
+    let alert = DETECTOR
+        .write()
+        .expect("failed to get DETECTOR write lock")
+        .observe(observation);
+    let alert = match alert {
+        Some(v) => v,
+        None => return,
+    };
+
+    if PAUSE_ON_DRIFT.load(Ordering::SeqCst) {
+        error!("markup drift detected, pause the crawl, update the profile and run again to resume\n{}", alert);
+        PAUSED.store(true, Ordering::SeqCst);
+    } else {
+        warn!("markup drift detected, ignored\n{}", alert);
+    }
+}
+
+/// What was extracted from one book page.
+#[derive(Debug)]
+struct Observation {
+    url: String,
+    fields: Vec<(&'static str, bool)>,
+    unknown_labels: Vec<String>,
+}
+
+#[derive(Debug)]
+struct FieldDrop {
+    field: &'static str,
+    baseline_rate: f32,
+    window_rate: f32,
+    /// books of the window the field is missing from
+    sample_urls: Vec<String>,
+}
+
+#[derive(Debug)]
+struct NewLabel {
+    label: String,
+    books: usize,
+    sample_urls: Vec<String>,
+}
+
+/// Why the crawl is paused, printed for whoever updates the profile.
+#[derive(Debug)]
+struct DriftAlert {
+    window_size: usize,
+    baseline_size: usize,
+    dropped: Vec<FieldDrop>,
+    new_labels: Vec<NewLabel>,
+}
+
+impl fmt::Display for DriftAlert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "last {} books compared with the {} before",
+            self.window_size, self.baseline_size
+        )?;
+        for drop in self.dropped.iter() {
+            writeln!(
+                f,
+                "  fill rate dropped, field= {:?}, before= {:.0}%, now= {:.0}%, samples= {:?}",
+                drop.field,
+                drop.baseline_rate * 100.0,
+                drop.window_rate * 100.0,
+                drop.sample_urls
+            )?;
+        }
+        for new_label in self.new_labels.iter() {
+            writeln!(
+                f,
+                "  new basic info label, label= {:?}, books= {}, samples= {:?}",
+                new_label.label, new_label.books, new_label.sample_urls
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Compares the last `WINDOW_SIZE` books with every book before them.
+#[derive(Debug, Default)]
+struct Detector {
+    window: VecDeque<Observation>,
+    baseline_size: usize,
+    /// field -> books of the baseline it is present in
+    baseline_present: BTreeMap<&'static str, usize>,
+    /// unknown labels already seen in the baseline
+    known_labels: HashSet<String>,
+}
+
+impl Detector {
+    fn observe(&mut self, observation: Observation) -> Option<DriftAlert> {
+        self.window.push_back(observation);
+        if self.window.len() <= WINDOW_SIZE {
+            return None;
+        }
+        if let Some(oldest) = self.window.pop_front() {
+            self.add_to_baseline(oldest);
+        }
+        if self.baseline_size < WINDOW_SIZE {
+            return None;
+        }
+
+        let alert = DriftAlert {
+            window_size: self.window.len(),
+            baseline_size: self.baseline_size,
+            dropped: self.dropped_fields(),
+            new_labels: self.new_labels(),
+        };
+        if alert.dropped.is_empty() && alert.new_labels.is_empty() {
+            return None;
+        }
+        // the next alert needs a full window of its own
+        while let Some(oldest) = self.window.pop_front() {
+            self.add_to_baseline(oldest);
+        }
+        Some(alert)
+    }
+
+    fn add_to_baseline(&mut self, observation: Observation) {
+        self.baseline_size += 1;
+        for (field, present) in observation.fields {
+            let count = self.baseline_present.entry(field).or_insert(0);
+            if present {
+                *count += 1;
+            }
+        }
+        self.known_labels.extend(observation.unknown_labels);
+    }
+
+    fn dropped_fields(&self) -> Vec<FieldDrop> {
+        let mut dropped = Vec::new();
+        for (field, baseline_present) in self.baseline_present.iter() {
+            let baseline_rate = *baseline_present as f32 / self.baseline_size as f32;
+            if baseline_rate < MIN_BASELINE_RATE {
+                continue;
+            }
+
+            let missing_urls: Vec<&str> = self
+                .window
+                .iter()
+                .filter(|o| o.fields.iter().any(|(f, present)| f == field && !present))
+                .map(|o| o.url.as_str())
+                .collect();
+            let window_rate = 1.0 - missing_urls.len() as f32 / self.window.len() as f32;
+            if baseline_rate * MAX_DROP_RATIO < window_rate {
+                continue;
+            }
+            dropped.push(FieldDrop {
+                field,
+                baseline_rate,
+                window_rate,
+                sample_urls: sample_urls(missing_urls),
+            });
+        }
+        dropped
+    }
+
+    fn new_labels(&self) -> Vec<NewLabel> {
+        // label -> books of the window showing it
+        let mut label_urls: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        for observation in self.window.iter() {
+            for label in observation.unknown_labels.iter() {
+                if !self.known_labels.contains(label) {
+                    label_urls
+                        .entry(label.as_str())
+                        .or_default()
+                        .push(observation.url.as_str());
+                }
+            }
+        }
+        label_urls
+            .into_iter()
+            .filter(|(_, urls)| MIN_NEW_LABEL_BOOKS <= urls.len())
+            .map(|(label, urls)| NewLabel {
+                label: label.to_owned(),
+                books: urls.len(),
+                sample_urls: sample_urls(urls),
+            })
+            .collect()
+    }
+}
+
+fn sample_urls(urls: Vec<&str>) -> Vec<String> {
+    urls.into_iter()
+        .take(MAX_SAMPLE_URLS)
+        .map(|url| url.to_owned())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn observation(idx: usize, title: bool, unknown_labels: &[&str]) -> Observation {
+        Observation {
+            url: format!("https://book.douban.com/subject/{}/", idx),
+            fields: vec![("title", title), ("isbn", true)],
+            unknown_labels: unknown_labels.iter().map(|l| l.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn stable_pages_raise_nothing() {
+        let mut detector = Detector::default();
+        for idx in 0..(WINDOW_SIZE * 4) {
+            // a rare label in the middle of the crawl is an odd book
+            let labels: &[&str] = if idx == WINDOW_SIZE * 3 {
+                &["印次"]
+            } else {
+                &[]
+            };
+            assert!(detector.observe(observation(idx, true, labels)).is_none());
+        }
+    }
+
+    #[test]
+    fn fill_rate_drop_raises_an_alert() {
+        let mut detector = Detector::default();
+        for idx in 0..(WINDOW_SIZE * 2) {
+            assert!(detector.observe(observation(idx, true, &[])).is_none());
+        }
+        let alert = (0..WINDOW_SIZE)
+            .find_map(|idx| detector.observe(observation(1000 + idx, false, &[])))
+            .expect("a missing title on every new page should raise an alert");
+        assert_eq!(alert.dropped.len(), 1);
+        assert_eq!(alert.dropped[0].field, "title");
+        assert_eq!(alert.dropped[0].sample_urls.len(), MAX_SAMPLE_URLS);
+        assert!(alert.new_labels.is_empty());
+    }
+
+    #[test]
+    fn new_labels_raise_an_alert() {
+        let mut detector = Detector::default();
+        for idx in 0..(WINDOW_SIZE * 2) {
+            assert!(detector
+                .observe(observation(idx, true, &["印次"]))
+                .is_none());
+        }
+        let alert = (0..MIN_NEW_LABEL_BOOKS)
+            .find_map(|idx| detector.observe(observation(1000 + idx, true, &["作者:"])))
+            .expect("a new label on several pages should raise an alert");
+        assert!(alert.dropped.is_empty());
+        assert_eq!(alert.new_labels.len(), 1);
+        assert_eq!(alert.new_labels[0].label, "作者:");
+        assert_eq!(alert.new_labels[0].books, MIN_NEW_LABEL_BOOKS);
+    }
+}
@@ -0,0 +1,164 @@
+use std::io;
+use thiserror::Error;
+
+/// What the crawler should do with the item that failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ErrorAction {
+    /// try again right away, the failure is likely transient
+    Retry,
+    /// give up on this item and move on
+    Skip,
+    /// put the item back and try again later, e.g. with another proxy
+    Requeue,
+    /// stop the crawl, nothing after this is going to work
+    Abort,
+}
+
+#[derive(Debug, Error)]
+pub(crate) enum FetchError {
+    #[error("no proxy available")]
+    NoProxy,
+    #[error("failed to build client, e= {0}")]
+    Client(#[source] reqwest::Error),
+    #[error("request failed, url= {url:?}, e= {source}")]
+    Request {
+        url: String,
+        #[source]
+        source: reqwest::Error,
+    },
+    #[error("page not found, url= {url:?}")]
+    NotFound { url: String },
+    #[error("banned by server, status= {status:?}, url= {url:?}")]
+    Banned { url: String, status: u16 },
+    #[error("disallowed by robots.txt, url= {url:?}")]
+    Disallowed { url: String },
+    #[error("unexpected status, status= {status:?}, url= {url:?}")]
+    Status { url: String, status: u16 },
+    #[error("body too large, max= {limit:?}")]
+    BodyTooLarge { limit: u64 },
+    #[error("unsupported content encoding, content_encoding= {0:?}")]
+    UnsupportedEncoding(String),
+    #[error("failed to read body, e= {0}")]
+    Body(#[source] io::Error),
+    #[error("body is not a known image format, url= {url:?}")]
+    NotAnImage { url: String },
+}
+
+#[derive(Debug, Error)]
+pub(crate) enum ParseError {
+    #[error("invalid selector, selector= {selector:?}, e= {reason}")]
+    Selector { selector: String, reason: String },
+    #[error("element not found, element= {element:?}, url= {url:?}")]
+    MissingElement { element: &'static str, url: String },
+    #[error("book blocked by validation, issues= {issues:?}, url= {url:?}")]
+    Incomplete { url: String, issues: Vec<String> },
+}
+
+#[derive(Debug, Error)]
+pub(crate) enum StoreError {
+    #[error("store io error, path= {path:?}, e= {source}")]
+    Io {
+        path: String,
+        #[source]
+        source: io::Error,
+    },
+}
+
+#[derive(Debug, Error)]
+pub(crate) enum Error {
+    #[error(transparent)]
+    Fetch(#[from] FetchError),
+    #[error(transparent)]
+    Parse(#[from] ParseError),
+    #[error(transparent)]
+    Store(#[from] StoreError),
+}
+
+// ENOSPC, EROFS and EDQUOT on linux
+const FATAL_OS_ERRORS: &[i32] = &[28, 30, 122];
+
+/// Disk full or no permission, every book after this is going to fail the same way.
+fn is_fatal_io(e: &io::Error) -> bool {
+    e.kind() == io::ErrorKind::PermissionDenied
+        || matches!(e.raw_os_error(), Some(code) if FATAL_OS_ERRORS.contains(&code))
+}
+
+impl Error {
+    pub(crate) fn action(&self) -> ErrorAction {
+        match self {
+            Error::Fetch(e) => match e {
+                FetchError::NoProxy => ErrorAction::Abort,
+                FetchError::Client(_) | FetchError::Request { .. } => ErrorAction::Retry,
+                FetchError::Status { status, .. } if 500 <= *status => ErrorAction::Retry,
+                FetchError::Banned { .. } => ErrorAction::Requeue,
+                FetchError::NotFound { .. }
+                | FetchError::Disallowed { .. }
+                | FetchError::Status { .. }
+                | FetchError::BodyTooLarge { .. }
+                | FetchError::UnsupportedEncoding(_)
+                | FetchError::Body(_)
+                | FetchError::NotAnImage { .. } => ErrorAction::Skip,
+            },
+            // a bad selector is a bug, every page is going to fail the same way
+            Error::Parse(ParseError::Selector { .. }) => ErrorAction::Abort,
+            Error::Parse(ParseError::MissingElement { .. })
+            | Error::Parse(ParseError::Incomplete { .. }) => ErrorAction::Skip,
+            Error::Store(StoreError::Io { source, .. }) => {
+                if is_fatal_io(source) {
+                    ErrorAction::Abort
+                } else {
+                    ErrorAction::Skip
+                }
+            }
+        }
+    }
+
+    /// Short stable name, used as the key in run statistics.
+    pub(crate) fn kind(&self) -> &'static str {
+        match self {
+            Error::Fetch(e) => match e {
+                FetchError::NoProxy => "fetch.no_proxy",
+                FetchError::Client(_) => "fetch.client",
+                FetchError::Request { .. } => "fetch.request",
+                FetchError::NotFound { .. } => "fetch.not_found",
+                FetchError::Banned { .. } => "fetch.banned",
+                FetchError::Disallowed { .. } => "fetch.disallowed",
+                FetchError::Status { .. } => "fetch.status",
+                FetchError::BodyTooLarge { .. } => "fetch.body_too_large",
+                FetchError::UnsupportedEncoding(_) => "fetch.unsupported_encoding",
+                FetchError::Body(_) => "fetch.body",
+                FetchError::NotAnImage { .. } => "fetch.not_an_image",
+            },
+            Error::Parse(e) => match e {
+                ParseError::Selector { .. } => "parse.selector",
+                ParseError::MissingElement { .. } => "parse.missing_element",
+                ParseError::Incomplete { .. } => "parse.incomplete",
+            },
+            Error::Store(e) => match e {
+                StoreError::Io { .. } => "store.io",
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store_error(e: io::Error) -> Error {
+        Error::Store(StoreError::Io {
+            path: "books/上_1".to_owned(),
+            source: e,
+        })
+    }
+
+    #[test]
+    fn only_fatal_store_errors_abort() {
+        let not_found = store_error(io::Error::from(io::ErrorKind::NotFound));
+        assert_eq!(not_found.action(), ErrorAction::Skip);
+        let denied = store_error(io::Error::from(io::ErrorKind::PermissionDenied));
+        assert_eq!(denied.action(), ErrorAction::Abort);
+        let full = store_error(io::Error::from_raw_os_error(28));
+        assert_eq!(full.action(), ErrorAction::Abort);
+    }
+}
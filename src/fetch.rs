@@ -1,9 +1,12 @@
-use anyhow::Context;
+use crate::error::FetchError;
+use encoding_rs::{Encoding, UTF_8};
 use lazy_static::lazy_static;
 use log::{debug, trace};
 use rand::Rng;
-use reqwest::blocking::Client;
+use reqwest::blocking::{Client, Response};
 use reqwest::header;
+use reqwest::StatusCode;
+use std::io::Read;
 use std::sync::RwLock;
 use std::time;
 
@@ -18,9 +21,11 @@ fn set_last_fetch_time() {
     *last_fetch_time = time::Instant::now();
 }
 
-fn sleep_if_fetch_too_fast() {
-    // the duration from last fetch is randomly generated
-    let expect_duration: u128 = rand::thread_rng().gen_range(2000, 5000);
+fn sleep_if_fetch_too_fast(crawl_delay: Option<time::Duration>) {
+    // the duration from last fetch is randomly generated, but never below the crawl delay
+    let expect_duration: u128 = rand::thread_rng()
+        .gen_range(2000, 5000)
+        .max(crawl_delay.map(|d| d.as_millis()).unwrap_or_default());
     let actual_duratioin = LAST_FETCH_TIME
         .read()
         .expect("failed to get LAST_FETCH_TIME read lock")
@@ -43,10 +48,9 @@ fn sleep_if_fetch_too_fast() {
 
 pub(crate) fn get_default_headers() -> anyhow::Result<header::HeaderMap> {
     const USER_AGENT_VALUE: &str = r#"Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/81.0.4044.138 Safari/537.36"#;
-    const HOST_VALUE: &str = r#"book.douban.com"#;
     const CONNECTION_VALUE: &str = r#"keep-alive"#;
     const ACCEPT_VALUE: &str = r#"text/html,application/xhtml+xml,application/xml;q=0.9,image/webp,image/apng,*/*;q=0.8,application/signed-exchange;v=b3;q=0.9"#;
-    const ACCEPT_ENCODING_VALUE: &str = r#"gzip deflate"#;
+    const ACCEPT_ENCODING_VALUE: &str = r#"gzip, deflate, br"#;
     const ACCEPT_LANGUAGE_VALUE: &str = r#"zh-CN,zh;q=0.9"#;
     // use your own cookie
     const COOKIE_VALUE: &str = r#""#;
@@ -54,7 +58,6 @@ pub(crate) fn get_default_headers() -> anyhow::Result<header::HeaderMap> {
     let mut headers = header::HeaderMap::new();
 
     headers.insert(header::USER_AGENT, USER_AGENT_VALUE.parse()?);
-    headers.insert(header::HOST, HOST_VALUE.parse()?);
     headers.insert(header::CONNECTION, CONNECTION_VALUE.parse()?);
     headers.insert(header::ACCEPT, ACCEPT_VALUE.parse()?);
     headers.insert(header::ACCEPT_ENCODING, ACCEPT_ENCODING_VALUE.parse()?);
@@ -64,20 +67,47 @@ pub(crate) fn get_default_headers() -> anyhow::Result<header::HeaderMap> {
     Ok(headers)
 }
 
-fn get_client() -> anyhow::Result<Client> {
+fn get_client() -> Result<Client, FetchError> {
     let proxy_info = crate::proxy::get_proxy_to_use()?;
     let proxy_str = format!("http://{}:{}", proxy_info.ip, proxy_info.port);
     debug!("used proxy: {:?}", proxy_str);
-    let proxy = reqwest::Proxy::http(proxy_str.as_str())?;
-    Ok(Client::builder()
-        .default_headers(get_default_headers()?)
+    let proxy = reqwest::Proxy::http(proxy_str.as_str()).map_err(FetchError::Client)?;
+    let default_headers = get_default_headers().expect("default headers should be valid");
+    Client::builder()
+        .default_headers(default_headers)
         .proxy(proxy)
-        .build()?)
+        .build()
+        .map_err(FetchError::Client)
 }
 
-pub(crate) fn get_page(url: &str, referrer: &str) -> anyhow::Result<String> {
+pub(crate) fn get_page(url: &str, referrer: &str) -> Result<String, FetchError> {
+    let crawl_delay = crate::robots::check(url)?;
+    fetch_page_with_delay(url, referrer, crawl_delay)
+}
+
+/// Fetches the page without looking at robots.txt, only used to get robots.txt itself.
+pub(crate) fn fetch_page(url: &str, referrer: &str) -> Result<String, FetchError> {
+    fetch_page_with_delay(url, referrer, None)
+}
+
+/// Fetches a binary resource, e.g. a cover image, the same way pages are fetched.
+pub(crate) fn get_bytes(url: &str, referrer: &str) -> Result<Vec<u8>, FetchError> {
+    let crawl_delay = crate::robots::check(url)?;
+    let resp = send(url, referrer, crawl_delay)?;
+    let content_encoding = header_value(&resp, header::CONTENT_ENCODING);
+    check_content_length(&resp)?;
+
+    let raw = read_limited(resp, MAX_BODY_SIZE)?;
+    decompress(raw, content_encoding.as_str())
+}
+
+fn send(
+    url: &str,
+    referrer: &str,
+    crawl_delay: Option<time::Duration>,
+) -> Result<Response, FetchError> {
     // control fetch speed
-    sleep_if_fetch_too_fast();
+    sleep_if_fetch_too_fast(crawl_delay);
     set_last_fetch_time();
 
     let client = get_client()?;
@@ -85,12 +115,318 @@ pub(crate) fn get_page(url: &str, referrer: &str) -> anyhow::Result<String> {
         .get(url)
         .header(header::REFERER, referrer)
         .send()
-        .with_context(|| format!("failed to get page, url= {:?}", url))?;
+        .map_err(|e| FetchError::Request {
+            url: url.to_owned(),
+            source: e,
+        })?;
     debug!("response status: {:?}, url= {:?}", resp.status(), url);
-    let text = resp
-        .text()
-        .with_context(|| format!("faild to get resp text, url= {:?}", url))?;
+    check_status(url, resp.status())?;
+
+    Ok(resp)
+}
+
+fn fetch_page_with_delay(
+    url: &str,
+    referrer: &str,
+    crawl_delay: Option<time::Duration>,
+) -> Result<String, FetchError> {
+    let resp = send(url, referrer, crawl_delay)?;
+    let text = read_text(resp)?;
     trace!("response text: {:?}", text);
 
+    // douban redirects suspicious clients to its verification page
+    if crate::profile::get().fetch.ban_page.matches(&text) {
+        return Err(FetchError::Banned {
+            url: url.to_owned(),
+            status: StatusCode::OK.as_u16(),
+        });
+    }
+
     Ok(text)
 }
+
+fn check_status(url: &str, status: StatusCode) -> Result<(), FetchError> {
+    let url = url.to_owned();
+    match status {
+        s if s.is_success() => Ok(()),
+        StatusCode::NOT_FOUND | StatusCode::GONE => Err(FetchError::NotFound { url }),
+        StatusCode::FORBIDDEN | StatusCode::TOO_MANY_REQUESTS | StatusCode::IM_A_TEAPOT => {
+            Err(FetchError::Banned {
+                url,
+                status: status.as_u16(),
+            })
+        }
+        _ => Err(FetchError::Status {
+            url,
+            status: status.as_u16(),
+        }),
+    }
+}
+
+// max size of both the raw and the decompressed body
+const MAX_BODY_SIZE: u64 = 8 * 1024 * 1024;
+
+pub(crate) fn read_text(resp: Response) -> Result<String, FetchError> {
+    let content_type = header_value(&resp, header::CONTENT_TYPE);
+    let content_encoding = header_value(&resp, header::CONTENT_ENCODING);
+    check_content_length(&resp)?;
+
+    let raw = read_limited(resp, MAX_BODY_SIZE)?;
+    decode_text(raw, content_encoding.as_str(), content_type.as_str())
+}
+
+/// The body as text, `content_encoding` and `content_type` are lower case header values.
+fn decode_text(
+    raw: Vec<u8>,
+    content_encoding: &str,
+    content_type: &str,
+) -> Result<String, FetchError> {
+    let body = decompress(raw, content_encoding)?;
+    let encoding = detect_encoding(content_type, body.as_slice());
+    let (text, used_encoding, had_errors) = encoding.decode(body.as_slice());
+    if had_errors {
+        debug!(
+            "malformed sequences found when decoding body, encoding= {:?}",
+            used_encoding.name()
+        );
+    }
+
+    Ok(text.into_owned())
+}
+
+fn check_content_length(resp: &Response) -> Result<(), FetchError> {
+    match resp.content_length() {
+        Some(content_length) if MAX_BODY_SIZE < content_length => Err(FetchError::BodyTooLarge {
+            limit: MAX_BODY_SIZE,
+        }),
+        _ => Ok(()),
+    }
+}
+
+fn header_value(resp: &Response, name: header::HeaderName) -> String {
+    resp.headers()
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase()
+}
+
+fn read_limited<R: Read>(reader: R, limit: u64) -> Result<Vec<u8>, FetchError> {
+    let mut buf = Vec::new();
+    reader
+        .take(limit + 1)
+        .read_to_end(&mut buf)
+        .map_err(FetchError::Body)?;
+    if limit < buf.len() as u64 {
+        return Err(FetchError::BodyTooLarge { limit });
+    }
+
+    Ok(buf)
+}
+
+/// Undoes every coding of the header, "gzip, br" was gzipped first so brotli is undone first.
+fn decompress(raw: Vec<u8>, content_encoding: &str) -> Result<Vec<u8>, FetchError> {
+    content_encoding
+        .split(',')
+        .map(|coding| coding.trim())
+        .rev()
+        .try_fold(raw, decompress_one)
+}
+
+fn decompress_one(raw: Vec<u8>, content_coding: &str) -> Result<Vec<u8>, FetchError> {
+    match content_coding {
+        "" | "identity" => Ok(raw),
+        "gzip" | "x-gzip" => {
+            read_limited(flate2::read::GzDecoder::new(raw.as_slice()), MAX_BODY_SIZE)
+        }
+        "deflate" => {
+            // "deflate" should be zlib wrapped, but some servers send raw deflate
+            read_limited(
+                flate2::read::ZlibDecoder::new(raw.as_slice()),
+                MAX_BODY_SIZE,
+            )
+            .or_else(|_| {
+                read_limited(
+                    flate2::read::DeflateDecoder::new(raw.as_slice()),
+                    MAX_BODY_SIZE,
+                )
+            })
+        }
+        "br" => read_limited(
+            brotli::Decompressor::new(raw.as_slice(), 4096),
+            MAX_BODY_SIZE,
+        ),
+        _ => Err(FetchError::UnsupportedEncoding(content_coding.to_owned())),
+    }
+}
+
+fn detect_encoding(content_type: &str, body: &[u8]) -> &'static Encoding {
+    // BOM > Content-Type charset > meta charset > utf-8
+    if let Some((encoding, _)) = Encoding::for_bom(body) {
+        return encoding;
+    }
+
+    if let Some(encoding) = charset_from_content_type(content_type) {
+        return encoding;
+    }
+
+    const META_SNIFF_LEN: usize = 1024;
+    let head = &body[..body.len().min(META_SNIFF_LEN)];
+    let head = String::from_utf8_lossy(head).to_ascii_lowercase();
+    if let Some(encoding) = charset_from_meta(head.as_str()) {
+        return encoding;
+    }
+
+    UTF_8
+}
+
+fn charset_from_content_type(content_type: &str) -> Option<&'static Encoding> {
+    content_type
+        .split(';')
+        .filter_map(|param| {
+            let mut kv = param.splitn(2, '=');
+            match (kv.next(), kv.next()) {
+                (Some(k), Some(v)) if k.trim() == "charset" => Some(v),
+                _ => None,
+            }
+        })
+        .next()
+        .and_then(|v| Encoding::for_label(v.trim().trim_matches(&['"', '\''][..]).as_bytes()))
+}
+
+fn charset_from_meta(head: &str) -> Option<&'static Encoding> {
+    // covers both <meta charset="gbk"> and
+    // <meta http-equiv="Content-Type" content="text/html; charset=gbk">
+    let pos = head.find("charset=")?;
+    let value = head[pos + "charset=".len()..].trim_start_matches(&['"', '\''][..]);
+    let end = value
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+        .unwrap_or(value.len());
+    Encoding::for_label(&value.as_bytes()[..end])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    const PAGE: &str = "<html><head><title>解忧杂货店</title></head><body>东野圭吾</body></html>";
+
+    fn compress<W: Write>(mut encoder: W, data: &[u8]) -> W {
+        encoder
+            .write_all(data)
+            .expect("write to memory never fails");
+        encoder
+    }
+
+    #[test]
+    fn decompress_each_encoding() {
+        use flate2::write::{DeflateEncoder, GzEncoder, ZlibEncoder};
+        use flate2::Compression;
+
+        let data = PAGE.as_bytes();
+        let gzip = compress(GzEncoder::new(Vec::new(), Compression::default()), data)
+            .finish()
+            .expect("gzip");
+        let zlib = compress(ZlibEncoder::new(Vec::new(), Compression::default()), data)
+            .finish()
+            .expect("zlib");
+        let raw_deflate = compress(
+            DeflateEncoder::new(Vec::new(), Compression::default()),
+            data,
+        )
+        .finish()
+        .expect("deflate");
+        let br =
+            compress(brotli::CompressorWriter::new(Vec::new(), 4096, 5, 22), data).into_inner();
+        let gzip_then_br = compress(
+            brotli::CompressorWriter::new(Vec::new(), 4096, 5, 22),
+            gzip.as_slice(),
+        )
+        .into_inner();
+
+        let cases = [
+            ("", data.to_vec()),
+            ("identity", data.to_vec()),
+            ("gzip", gzip),
+            ("deflate", zlib),
+            ("deflate", raw_deflate),
+            ("br", br),
+            ("gzip, br", gzip_then_br),
+        ];
+        for (content_encoding, raw) in cases.iter() {
+            let text = decode_text(raw.clone(), content_encoding, "text/html; charset=utf-8")
+                .unwrap_or_else(|e| panic!("{:?} should decode, e= {:?}", content_encoding, e));
+            assert_eq!(text, PAGE, "content_encoding= {:?}", content_encoding);
+        }
+        assert!(matches!(
+            decode_text(data.to_vec(), "compress", ""),
+            Err(FetchError::UnsupportedEncoding(_))
+        ));
+        assert!(matches!(
+            decode_text(data.to_vec(), "identity, compress", ""),
+            Err(FetchError::UnsupportedEncoding(_))
+        ));
+    }
+
+    #[test]
+    fn bodies_over_the_cap_are_rejected() {
+        assert!(matches!(
+            read_limited(&[0u8; 11][..], 10),
+            Err(FetchError::BodyTooLarge { limit: 10 })
+        ));
+        assert_eq!(
+            read_limited(&[0u8; 10][..], 10).map(|v| v.len()).ok(),
+            Some(10)
+        );
+
+        // small on the wire, too large once decompressed
+        let zeros = vec![0u8; MAX_BODY_SIZE as usize + 1];
+        let gzip = compress(
+            flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default()),
+            zeros.as_slice(),
+        )
+        .finish()
+        .expect("gzip");
+        assert!(matches!(
+            decode_text(gzip, "gzip", ""),
+            Err(FetchError::BodyTooLarge { .. })
+        ));
+    }
+
+    #[test]
+    fn detect_charsets() {
+        let gbk_page = "<html><head><meta http-equiv=\"Content-Type\" content=\"text/html; charset=gbk\"></head><body>东野圭吾</body></html>";
+        let (gbk, _, _) = encoding_rs::GBK.encode(gbk_page);
+        // declared only in the page
+        let text = decode_text(gbk.to_vec(), "", "text/html").expect("gbk page");
+        assert_eq!(text, gbk_page);
+
+        let meta_charset = "<meta charset='GB2312'><p>小说</p>";
+        let (gb2312, _, _) = encoding_rs::GBK.encode(meta_charset);
+        assert_eq!(
+            decode_text(gb2312.to_vec(), "", "").expect("gb2312 page"),
+            meta_charset
+        );
+
+        // the header wins over the page
+        let (gbk_body, _, _) = encoding_rs::GBK.encode("<meta charset=\"utf-8\">小说");
+        assert_eq!(
+            decode_text(gbk_body.to_vec(), "", "text/html; charset=\"GBK\"").expect("gbk body"),
+            "<meta charset=\"utf-8\">小说"
+        );
+
+        // and the bom over both
+        let mut bom = vec![0xEF, 0xBB, 0xBF];
+        bom.extend_from_slice("小说".as_bytes());
+        assert_eq!(
+            decode_text(bom, "", "text/html; charset=gbk").expect("utf-8 with bom"),
+            "小说"
+        );
+        assert_eq!(
+            decode_text(PAGE.as_bytes().to_vec(), "", "").expect("utf-8"),
+            PAGE
+        );
+    }
+}
@@ -0,0 +1,220 @@
+use crate::parser::{book_page, root_page, search_page, tag_page};
+use anyhow::Context;
+use log::info;
+use std::fmt::Write;
+use std::fs;
+use std::path;
+
+// saved pages, and next to each "<name>.golden" with what the parsers made of it,
+// relative to the working directory, use --fixtures=path to record elsewhere
+pub(crate) const FIXTURE_DIR: &str = "tests/fixtures";
+
+#[derive(Debug, Clone, Copy)]
+enum PageKind {
+    Root,
+    Tag,
+    Search,
+    Book,
+    Kuaidaili,
+    Xicidaili,
+}
+
+struct Fixture {
+    /// file name without ".html"
+    name: &'static str,
+    kind: PageKind,
+    /// where the page was saved from
+    url: &'static str,
+}
+
+const FIXTURES: &[Fixture] = &[
+    Fixture {
+        name: "root",
+        kind: PageKind::Root,
+        url: "https://book.douban.com/tag/",
+    },
+    Fixture {
+        name: "tag",
+        kind: PageKind::Tag,
+        url: "https://book.douban.com/tag/%E5%B0%8F%E8%AF%B4?start=0&type=T",
+    },
+    Fixture {
+        name: "tag_empty",
+        kind: PageKind::Tag,
+        url: "https://book.douban.com/tag/%E5%B0%8F%E8%AF%B4?start=980&type=T",
+    },
+    Fixture {
+        name: "search",
+        kind: PageKind::Search,
+        url: "https://search.douban.com/book/subject_search?search_text=%E4%B8%9C%E9%87%8E%E5%9C%AD%E5%90%BE&cat=1001&start=0",
+    },
+    Fixture {
+        name: "search_next",
+        kind: PageKind::Search,
+        url: "https://search.douban.com/book/subject_search?search_text=%E4%B8%9C%E9%87%8E%E5%9C%AD%E5%90%BE&cat=1001&start=15",
+    },
+    Fixture {
+        name: "book",
+        kind: PageKind::Book,
+        url: "https://book.douban.com/subject/25862578/",
+    },
+    Fixture {
+        name: "book_insufficient_ratings",
+        kind: PageKind::Book,
+        url: "https://book.douban.com/subject/35123456/",
+    },
+    Fixture {
+        name: "kuaidaili",
+        kind: PageKind::Kuaidaili,
+        url: "https://www.kuaidaili.com/free/inha/1/",
+    },
+    Fixture {
+        name: "xicidaili",
+        kind: PageKind::Xicidaili,
+        url: "https://www.xicidaili.com/nn/1",
+    },
+];
+
+fn fixture_path(fixture_dir: &path::Path, fixture: &Fixture, extension: &str) -> path::PathBuf {
+    fixture_dir.join(format!("{}.{}", fixture.name, extension))
+}
+
+fn render_lines<T: std::fmt::Display>(out: &mut String, items: &[T]) {
+    for item in items {
+        writeln!(out, "{}", item).expect("write to string never fails");
+    }
+}
+
+/// What the parser of the page kind makes of the fixture, errors included.
+fn render(fixture: &Fixture, html: &str) -> String {
+    let mut out = String::new();
+    let res: anyhow::Result<()> = match fixture.kind {
+        PageKind::Root => root_page::parse_root_document(html)
+            .map(|hrefs| render_lines(&mut out, &hrefs))
+            .map_err(|e| e.into()),
+        PageKind::Tag => tag_page::parse_tag_document(html, fixture.url)
+            .map(|tag_page| {
+                writeln!(
+                    out,
+                    "page_count: {:?}\nnext_url: {:?}",
+                    tag_page.page_count, tag_page.next_url
+                )
+                .expect("write to string never fails");
+                render_lines(&mut out, &tag_page.items);
+            })
+            .map_err(|e| e.into()),
+        PageKind::Search => search_page::parse_search_document(html, fixture.url)
+            .map(|search_page| {
+                writeln!(out, "total: {:?}", search_page.total)
+                    .expect("write to string never fails");
+                render_lines(&mut out, &search_page.items);
+            })
+            .map_err(|e| e.into()),
+        PageKind::Book => book_page::parse_book_document(html, fixture.url)
+            .map(|mut book| {
+                book.quality = crate::validate::check(&book);
+                writeln!(out, "{}", book).expect("write to string never fails");
+            })
+            .map_err(|e| e.into()),
+        PageKind::Kuaidaili => crate::proxy::parse_kuaidaili_document(html, fixture.url)
+            .map(|proxy_infos| render_lines(&mut out, &proxy_infos)),
+        PageKind::Xicidaili => crate::proxy::parse_xicidaili_document(html)
+            .map(|proxy_infos| render_lines(&mut out, &proxy_infos)),
+    };
+    if let Err(e) = res {
+        writeln!(out, "error: {}", e).expect("write to string never fails");
+    }
+
+    out
+}
+
+fn render_fixture(fixture_dir: &path::Path, fixture: &Fixture) -> anyhow::Result<String> {
+    let html_path = fixture_path(fixture_dir, fixture, "html");
+    let html = fs::read_to_string(&html_path)
+        .with_context(|| format!("failed to read fixture, path= {:?}", html_path))?;
+    Ok(render(fixture, html.as_str()))
+}
+
+/// Parses every fixture again and overwrites its golden file, use cargo run record-goldens.
+///
+/// Run it after saving a new page or changing a parser on purpose, and review the diff.
+pub(crate) fn record(fixture_dir: &path::Path) -> anyhow::Result<()> {
+    for fixture in FIXTURES {
+        let golden_path = fixture_path(fixture_dir, fixture, "golden");
+        let rendered = render_fixture(fixture_dir, fixture)?;
+        fs::write(&golden_path, rendered)
+            .with_context(|| format!("failed to write golden, path= {:?}", golden_path))?;
+        info!(
+            "golden recorded, fixture= {:?}, kind= {:?}",
+            fixture.name, fixture.kind
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the tests always run from the source tree
+    fn fixture_dir() -> path::PathBuf {
+        path::Path::new(env!("CARGO_MANIFEST_DIR")).join(FIXTURE_DIR)
+    }
+
+    #[test]
+    fn parsers_match_goldens() {
+        let mut failures = Vec::new();
+        for fixture in FIXTURES {
+            let rendered =
+                render_fixture(&fixture_dir(), fixture).expect("fixture should be readable");
+            let golden_path = fixture_path(&fixture_dir(), fixture, "golden");
+            let golden = fs::read_to_string(&golden_path).unwrap_or_default();
+            if rendered == golden {
+                continue;
+            }
+
+            let line = rendered
+                .lines()
+                .zip(golden.lines().chain(std::iter::repeat("<eof>")))
+                .enumerate()
+                .find(|(_, (r, g))| r != g);
+            failures.push(match line {
+                Some((idx, (r, g))) => format!(
+                    "{}: line {}, parsed {:?}, golden {:?}",
+                    fixture.name,
+                    idx + 1,
+                    r,
+                    g
+                ),
+                None => format!("{}: golden has extra lines", fixture.name),
+            });
+        }
+
+        assert!(
+            failures.is_empty(),
+            "parsed output differs from the goldens, run `cargo run record-goldens` \
+             if the change is intended:\n{}",
+            failures.join("\n")
+        );
+    }
+
+    #[test]
+    fn every_fixture_is_listed() {
+        let mut listed: Vec<&str> = FIXTURES.iter().map(|f| f.name).collect();
+        listed.sort_unstable();
+        let mut on_disk: Vec<String> = fs::read_dir(fixture_dir())
+            .expect("fixture dir should exist")
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                entry
+                    .file_name()
+                    .to_string_lossy()
+                    .strip_suffix(".html")
+                    .map(|name| name.to_owned())
+            })
+            .collect();
+        on_disk.sort_unstable();
+        assert_eq!(listed, on_disk);
+    }
+}
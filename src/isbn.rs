@@ -0,0 +1,166 @@
+/// Strips hyphens, spaces and dots, "978-7-5327-4224-6" -> "9787532742246".
+pub(crate) fn normalize(raw: &str) -> String {
+    raw.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+fn digit(c: char) -> Option<u32> {
+    c.to_digit(10)
+}
+
+pub(crate) fn is_valid_isbn10(isbn: &str) -> bool {
+    let chars: Vec<char> = isbn.chars().collect();
+    if chars.len() != 10 {
+        return false;
+    }
+
+    let mut sum = 0;
+    for (idx, c) in chars.iter().enumerate() {
+        let v = match (idx, c) {
+            // only the check digit may be X
+            (9, 'X') => 10,
+            _ => match digit(*c) {
+                Some(v) => v,
+                None => return false,
+            },
+        };
+        sum += v * (10 - idx as u32);
+    }
+
+    sum % 11 == 0
+}
+
+pub(crate) fn is_valid_isbn13(isbn: &str) -> bool {
+    if isbn.len() != 13 || !(isbn.starts_with("978") || isbn.starts_with("979")) {
+        return false;
+    }
+
+    let mut sum = 0;
+    for (idx, c) in isbn.chars().enumerate() {
+        let v = match digit(c) {
+            Some(v) => v,
+            None => return false,
+        };
+        sum += if idx % 2 == 0 { v } else { v * 3 };
+    }
+
+    sum % 10 == 0
+}
+
+/// Expects a valid isbn-10.
+pub(crate) fn isbn10_to_isbn13(isbn10: &str) -> String {
+    let body = format!("978{}", &isbn10[..9]);
+    let sum: u32 = body
+        .chars()
+        .enumerate()
+        .map(|(idx, c)| {
+            let v = digit(c).unwrap_or_default();
+            if idx % 2 == 0 {
+                v
+            } else {
+                v * 3
+            }
+        })
+        .sum();
+    format!("{}{}", body, (10 - sum % 10) % 10)
+}
+
+/// Expects a valid isbn-13, only the 978 prefix has an isbn-10 form.
+pub(crate) fn isbn13_to_isbn10(isbn13: &str) -> Option<String> {
+    if !isbn13.starts_with("978") {
+        return None;
+    }
+
+    let body = &isbn13[3..12];
+    let sum: u32 = body
+        .chars()
+        .enumerate()
+        .map(|(idx, c)| digit(c).unwrap_or_default() * (10 - idx as u32))
+        .sum();
+    let check = match (11 - sum % 11) % 11 {
+        10 => String::from("X"),
+        v => v.to_string(),
+    };
+    Some(format!("{}{}", body, check))
+}
+
+/// Both forms of a valid isbn, `None` if the text is not one.
+pub(crate) fn parse(raw: &str) -> Option<(String, Option<String>)> {
+    let isbn = normalize(raw);
+    if is_valid_isbn13(isbn.as_str()) {
+        let isbn10 = isbn13_to_isbn10(isbn.as_str());
+        return Some((isbn, isbn10));
+    }
+    if is_valid_isbn10(isbn.as_str()) {
+        return Some((isbn10_to_isbn13(isbn.as_str()), Some(isbn)));
+    }
+
+    None
+}
+
+/// 统一书号, used before isbn in china, e.g. "10019·3284" or "7002-1085".
+///
+/// A 4 or 5 digit category, then the publisher and sequence number, "45.00" is a price and not one.
+pub(crate) fn looks_like_unified_book_number(raw: &str) -> bool {
+    let all_digits = |s: &str| s.chars().all(|c| c.is_ascii_digit());
+    let mut parts = raw.trim().split(&['·', '・', '-'][..]);
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(category), Some(sequence), None) => {
+            (4..=5).contains(&category.len())
+                && all_digits(category)
+                && (2..=6).contains(&sequence.len())
+                && all_digits(sequence)
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn isbn_checksums() {
+        assert!(is_valid_isbn10("7544270874"));
+        // check digit X
+        assert!(is_valid_isbn10("080442957X"));
+        assert!(!is_valid_isbn10("0804429579"));
+        assert!(!is_valid_isbn10("X804429570"));
+        assert!(!is_valid_isbn10("754427087"));
+
+        assert!(is_valid_isbn13("9787544270878"));
+        assert!(!is_valid_isbn13("9787544270875"));
+        // only the bookland prefixes
+        assert!(!is_valid_isbn13("1234567890128"));
+    }
+
+    #[test]
+    fn isbn_forms() {
+        assert_eq!(normalize("978-7-5442-7087-8"), "9787544270878");
+        assert_eq!(normalize("0 8044 2957 x"), "080442957X");
+        assert_eq!(isbn10_to_isbn13("7544270874"), "9787544270878");
+        assert_eq!(
+            isbn13_to_isbn10("9780804429573").as_deref(),
+            Some("080442957X")
+        );
+        assert_eq!(isbn13_to_isbn10("9791032305690"), None);
+        assert_eq!(
+            parse("7-5442-7087-4"),
+            Some(("9787544270878".to_owned(), Some("7544270874".to_owned())))
+        );
+        assert_eq!(parse("45.00"), None);
+    }
+
+    #[test]
+    fn unified_book_numbers() {
+        assert!(looks_like_unified_book_number("10019·3284"));
+        assert!(looks_like_unified_book_number("7002-1085"));
+        assert!(looks_like_unified_book_number(" 11019・146 "));
+        assert!(!looks_like_unified_book_number("45.00"));
+        assert!(!looks_like_unified_book_number("39-50"));
+        assert!(!looks_like_unified_book_number("10019·"));
+        assert!(!looks_like_unified_book_number("10019·3284·1"));
+    }
+}
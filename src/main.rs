@@ -1,152 +1,241 @@
-use crate::parser::{
-    book_page::get_and_parse_book_page,
-    root_page::get_and_parse_root_page,
-    tag_page::{get_and_parse_tag_page, get_max_tag_page_count},
-};
-use log::{debug, error, info, warn};
+use crate::crawler::Outcome;
+use log::{error, info, warn};
 use std::env;
 
+mod args;
 mod book;
+mod checkpoint;
+mod cover;
+mod crawler;
+mod dead_letter;
+mod drift;
+mod error;
 mod fetch;
+mod goldens;
+mod isbn;
 mod logs;
 mod parser;
+mod profile;
 mod proxy;
+mod robots;
+mod search;
+mod shutdown;
+mod stats;
 mod store;
+mod taxonomy;
 mod utils;
+mod validate;
+
+pub(crate) const EXIT_OK: i32 = 0;
+pub(crate) const EXIT_FAILURE: i32 = 1;
+// 128 + SIGINT, what shells report for a process stopped by ctrl-c
+pub(crate) const EXIT_INTERRUPTED: i32 = 130;
+// the markup changed, the crawl resumes from the checkpoint once the profile is updated
+pub(crate) const EXIT_PAUSED: i32 = 3;
 
-#[allow(clippy::cognitive_complexity)]
 fn main() {
+    let code = run();
+    log::logger().flush();
+    std::process::exit(code);
+}
+
+fn run() -> i32 {
     // init log
     if let Err(e) = crate::logs::init() {
         println!("init log failed, e= {:?}", e);
-        return;
+        return EXIT_FAILURE;
+    }
+
+    if let Err(e) = crate::shutdown::init() {
+        error!("init signal handler failed, e= {:?}", e);
+        return EXIT_FAILURE;
+    }
+
+    let args = match crate::args::parse(env::args()) {
+        Ok(args) => args,
+        Err(e) => {
+            error!("parse args failed, e= {:?}\n{}", e, crate::args::USAGE);
+            return EXIT_FAILURE;
+        }
+    };
+    let command = args.command.clone().unwrap_or_default();
+
+    // hosts we have permission to crawl, use --ignore-robots=host1,host2
+    crate::robots::init(args.values("ignore-robots"));
+
+    // where the parsers find things on the pages, use --profile=path/to/profile.toml
+    if let Err(e) = crate::profile::init(args.values("profile").into_iter().next()) {
+        error!("init profile failed, e= {:?}", e);
+        return EXIT_FAILURE;
+    }
+
+    // re-record the expected parser output of the saved pages in tests/fixtures
+    // use cargo run record-goldens, from the source tree or with --fixtures=path
+    if command == "record-goldens" {
+        let fixture_dir = args
+            .values("fixtures")
+            .into_iter()
+            .next()
+            .unwrap_or_else(|| crate::goldens::FIXTURE_DIR.to_owned());
+        if let Err(e) = crate::goldens::record(std::path::Path::new(fixture_dir.as_str())) {
+            error!("record goldens failed, e= {:?}", e);
+            return EXIT_FAILURE;
+        }
+
+        return EXIT_OK;
     }
 
     // get valid proxy ip pool
     // use cargo run proxy first
-    let args: Vec<String> = env::args().collect();
-    if args.len() >= 2 && args[1] == "proxy" {
+    if command == "proxy" {
         info!("get and parse proxy pool");
         if let Err(e) = crate::proxy::get_and_store_valid_proxies() {
             warn!("failed to get and store valid proxies, e= {:?}", e);
+            return EXIT_FAILURE;
         }
 
-        return;
+        return EXIT_OK;
     }
 
     // load valid proxy parsed by last step
     if let Err(e) = crate::proxy::init() {
         error!("init proxy failed, e= {:?}", e);
-        return;
+        return EXIT_FAILURE;
     }
 
     // init store
     if let Err(e) = crate::store::init() {
         error!("init store failed, e= {:?}", e);
-        return;
+        return EXIT_FAILURE;
     }
 
-    // parse root page
-    let tags_href = match get_and_parse_root_page() {
-        Ok(tags_href) => tags_href,
-        Err(e) => {
-            error!("{:?}", e);
-            return;
-        }
-    };
-    info!("parse root page success");
-    debug!("tags_href= {:?}", tags_href);
-
-    const TARGET_COUNT: usize = 10000;
-    let mut current_count: usize = crate::store::current_store_count();
-    info!("current store book count is {:?}", current_count);
-
-    const HOST: &str = "https://book.douban.com";
-    const ROOT_URL: &str = "https://book.douban.com/tag/";
-    for tag_href in tags_href {
-        // parse tag page, get max tag page count
-        let tag_url = format!("{}{}", HOST, tag_href);
-        let max_tag_page_count = match get_max_tag_page_count(tag_url.as_str(), ROOT_URL) {
-            Ok(v) => v,
+    // init dead letter store
+    if let Err(e) = crate::dead_letter::init() {
+        error!("init dead letter store failed, e= {:?}", e);
+        return EXIT_FAILURE;
+    }
+
+    // resume from where the last run stopped
+    if let Err(e) = crate::checkpoint::init() {
+        error!("init checkpoint failed, e= {:?}", e);
+        return EXIT_FAILURE;
+    }
+
+    // store cover images too, use --download-covers
+    if let Err(e) = crate::cover::init(args.flag("download-covers")) {
+        error!("init cover store failed, e= {:?}", e);
+        return EXIT_FAILURE;
+    }
+
+    // validation issues that keep a book from being stored, use --block-on=missing.title,suspicious.*
+    crate::validate::init(if args.flag("block-on") {
+        Some(args.values("block-on"))
+    } else {
+        None
+    });
+
+    // pause when the pages stop parsing like they used to, use --ignore-drift to only log it
+    crate::drift::init(!args.flag("ignore-drift"));
+
+    // the tags to crawl, use --categories=文学,流行 --exclude-categories=...
+    // --tags=... --exclude-tags=... --min-tag-count=10000
+    let min_count = match args.values("min-tag-count").first() {
+        Some(v) => match v.parse::<u32>() {
+            Ok(min_count) => Some(min_count),
             Err(e) => {
-                warn!(
-                    "failed to get max tag page count, ignore this tag, e= {:?}",
-                    e
-                );
-                continue;
+                error!("invalid min tag count, e= {:?}, min_tag_count= {:?}", e, v);
+                return EXIT_FAILURE;
             }
-        };
-        if max_tag_page_count == 0 {
-            warn!(
-                "max tag page count is zero, ignore this tag, tag_page_url= {:?}",
-                tag_url
-            );
-            continue;
-        }
-        info!(
-            "get max tag page count success, count= {:?}, tag_page_url= {:?}",
-            max_tag_page_count, tag_url
-        );
-
-        // trace all tag pages of a tag
-        const COUNT_PER_PAGE: i32 = 20;
-        for idx in 0..max_tag_page_count {
-            // parse tag page, get book urls
-            let tag_page_url = format!("{}?start={}&type=T", tag_url, idx * COUNT_PER_PAGE);
-            let referrer = if idx == 0 {
-                tag_url.clone()
-            } else {
-                format!("{}?start={}&type=T", tag_url, (idx - 1) * COUNT_PER_PAGE)
-            };
-            let books_url = match get_and_parse_tag_page(tag_page_url.as_str(), referrer.as_str()) {
-                Ok(books_url) => books_url,
-                Err(e) => {
-                    warn!("{:?}", e);
-                    continue;
-                }
-            };
-            info!("parse tag page suceess, url= {:?}", tag_page_url);
-
-            // parse book page, get book info
-            for book_url in books_url {
-                if TARGET_COUNT <= current_count {
-                    info!("reach target count, current count= {:?}, target count= {:?}, stop process.", current_count, TARGET_COUNT);
-                    std::process::exit(0);
-                }
+        },
+        None => None,
+    };
+    if let Err(e) = crate::taxonomy::init(crate::taxonomy::Filter {
+        include_categories: args.values("categories"),
+        exclude_categories: args.values("exclude-categories"),
+        include_tags: args.values("tags"),
+        exclude_tags: args.values("exclude-tags"),
+        min_count,
+    }) {
+        error!("init taxonomy failed, e= {:?}", e);
+        return EXIT_FAILURE;
+    }
 
-                if crate::store::is_already_store(book_url.as_str()) {
-                    info!("book has been stored, url= {:?}", book_url);
-                    continue;
+    // the orders tag pages are listed in, use --sort=T,S,R for composite, rating and date
+    let mut options = crate::crawler::Options::default();
+    if args.flag("sort") {
+        let mut sort_orders = Vec::new();
+        for name in args.values("sort") {
+            match crate::crawler::SortOrder::parse(name.as_str()) {
+                Some(v) if !sort_orders.contains(&v) => sort_orders.push(v),
+                Some(_) => (),
+                None => {
+                    error!("unknown sort order, sort= {:?}", name);
+                    return EXIT_FAILURE;
                 }
+            }
+        }
+        if !sort_orders.is_empty() {
+            options.sort_orders = sort_orders;
+        }
+    }
 
-                let book = match get_and_parse_book_page(book_url.as_str(), tag_page_url.as_str()) {
-                    Ok(book) => book,
+    // optional stages, use --follow-related and --all-comments
+    options.follow_related = args.flag("follow-related");
+    options.all_comments = args.flag("all-comments");
+    crate::crawler::init(options);
+
+    // find books through douban search instead of the tag listings, each file has a query per line
+    // use cargo run search --keywords=path --isbns=path --authors=path
+    let mut queries = Vec::new();
+    if command == "search" {
+        for (name, kind) in [
+            ("keywords", crate::search::QueryKind::Keyword),
+            ("isbns", crate::search::QueryKind::Isbn),
+            ("authors", crate::search::QueryKind::Author),
+        ]
+        .iter()
+        {
+            for path in args.values(name) {
+                match crate::search::load(path.as_str(), *kind) {
+                    Ok(v) => queries.extend(v),
                     Err(e) => {
-                        warn!("parse book page failed, e= {:?}, url= {:?}", e, book_url);
-                        continue;
+                        error!("load search queries failed, e= {:?}, path= {:?}", e, path);
+                        return EXIT_FAILURE;
                     }
-                };
-                let book_title = book.title.clone();
-                info!(
-                    "parse book success, title= {:?}, url= {:?}",
-                    book_title, book_url
-                );
-                if let Err(e) = crate::store::store(book_url.as_str(), book) {
-                    warn!("store book page failed, e= {:?}, url= {:?}", e, book_url);
-                    continue;
                 }
-
-                info!(
-                    "store book success, title= {:?}, url= {:?}",
-                    book_title, book_url
-                );
-                current_count += 1;
             }
-
-            info!(
-                "store all books in this tag page success, tag_page_url= {:?}",
-                tag_page_url
-            );
+        }
+        if queries.is_empty() {
+            error!("no search query, use --keywords, --isbns or --authors");
+            return EXIT_FAILURE;
         }
     }
+
+    // re-process urls failed in previous runs
+    // use cargo run retry-failed
+    let res = if command == "retry-failed" {
+        crate::crawler::retry_failed()
+    } else if command == "search" {
+        crate::crawler::search(queries)
+    } else {
+        crate::crawler::run()
+    };
+    let code = match res {
+        Ok(outcome) => {
+            info!("crawl stopped, outcome= {:?}", outcome);
+            match outcome {
+                Outcome::Finished | Outcome::TargetReached => EXIT_OK,
+                Outcome::Interrupted => EXIT_INTERRUPTED,
+                Outcome::Paused => EXIT_PAUSED,
+            }
+        }
+        Err(e) => {
+            error!("crawl aborted, kind= {:?}, e= {:?}", e.kind(), e);
+            EXIT_FAILURE
+        }
+    };
+
+    crate::checkpoint::flush();
+    info!("run stats: {}", crate::stats::snapshot());
+    code
 }
@@ -1,7 +1,17 @@
-use crate::book::{Book, Score};
+use crate::book::{
+    Book, Contributor, ContributorRole, IsbnStatus, RatingState, ReviewTeaser, Score, SubjectRef,
+    Tag,
+};
+use crate::error::{Error, ParseError};
 use crate::fetch::get_page;
-use crate::utils::get_selector;
+use crate::parser::comments_page::{parse_comment_count, parse_comment_items};
+use crate::parser::value::{
+    parse_contributor, parse_count, parse_directory, parse_page_count, parse_partial_date,
+    parse_price, parse_star_rating,
+};
+use crate::profile::{InfoField, SelectorChain};
 use crate::utils::node_ref_text;
+use crate::utils::parse_href_id;
 use ego_tree::NodeRef;
 use log::{debug, trace, warn};
 use scraper::element_ref::ElementRef;
@@ -9,37 +19,65 @@ use scraper::node::Node;
 use scraper::Html;
 use std::iter::Iterator;
 
-pub(crate) fn get_and_parse_book_page(book_page_url: &str, referrer: &str) -> anyhow::Result<Book> {
+pub(crate) fn get_and_parse_book_page(book_page_url: &str, referrer: &str) -> Result<Book, Error> {
     let resp_text = get_page(book_page_url, referrer)?;
-    let document = Html::parse_document(resp_text.as_str());
+    parse_book_document(resp_text.as_str(), book_page_url)
+}
 
-    let mut book: Book = Default::default();
+/// Parses a saved or fetched book page, `book_page_url` is where it came from.
+pub(crate) fn parse_book_document(html: &str, book_page_url: &str) -> Result<Book, Error> {
+    let document = Html::parse_document(html);
 
-    // location
-    book.location = book_page_url.to_owned();
+    let mut book = Book {
+        // location
+        location: book_page_url.to_owned(),
+        ..Default::default()
+    };
 
     // title
     parse_title(&document, &mut book)?;
 
+    // cover
+    parse_cover(&document, &mut book)?;
+
     // basic info
     parse_basic_info(&document, &mut book)?;
 
     // score
     parse_score(&document, &mut book)?;
 
+    // wish / reading / read
+    parse_collector(&document, &mut book)?;
+
     // related info
     parse_related_info(&document, &mut book)?;
 
+    // tags
+    parse_tags(&document, &mut book)?;
+
+    // editions, series and recommendations
+    parse_related_subjects(&document, &mut book)?;
+
+    // short comments and reviews
+    book.comment_count = parse_comment_count(&document)?;
+    book.comments = parse_comment_items(document.root_element(), book_page_url)?;
+    parse_reviews(&document, &mut book)?;
+
     Ok(book)
 }
 
 fn fill_star_value(score: &mut Score, star_value: f32, star_desc: &str, location: &str) -> bool {
-    match star_desc {
-        "5星" => score.five_star_pct = star_value,
-        "4星" => score.four_star_pct = star_value,
-        "3星" => score.three_star_pct = star_value,
-        "2星" => score.two_star_pct = star_value,
-        "1星" => score.one_star_pct = star_value,
+    let stars = crate::profile::get()
+        .book
+        .star_labels
+        .get(star_desc)
+        .copied();
+    match stars {
+        Some(5) => score.five_star_pct = Some(star_value),
+        Some(4) => score.four_star_pct = Some(star_value),
+        Some(3) => score.three_star_pct = Some(star_value),
+        Some(2) => score.two_star_pct = Some(star_value),
+        Some(1) => score.one_star_pct = Some(star_value),
         _ => {
             warn!(
                 "fill start value error, unknown star_desc, star_desc= {:?}, url= {:?}",
@@ -52,28 +90,56 @@ fn fill_star_value(score: &mut Score, star_value: f32, star_desc: &str, location
     true
 }
 
-fn parse_title(document: &Html, book: &mut Book) -> anyhow::Result<()> {
-    let h1_selector = get_selector("h1")?;
-    if let Some(h1) = document.select(&h1_selector).next() {
-        let span_selector = get_selector("span")?;
-        if let Some(span) = h1.select(&span_selector).next() {
-            let texts = span.text().collect::<Vec<_>>();
-            if !texts.is_empty() {
-                book.title = texts[0].trim().to_owned();
-            }
+fn parse_title(document: &Html, book: &mut Book) -> Result<(), ParseError> {
+    let profile = crate::profile::get();
+    if let Some(span) = profile.book.title.first(document.root_element())? {
+        let texts = span.text().collect::<Vec<_>>();
+        if !texts.is_empty() {
+            book.title = texts[0].trim().to_owned();
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_cover(document: &Html, book: &mut Book) -> Result<(), ParseError> {
+    let profile = crate::profile::get();
+    let div_mainpic = match profile.book.cover.first(document.root_element())? {
+        Some(v) => v,
+        None => {
+            warn!(
+                "parse cover error, div_mainpic is empty, url= {:?}",
+                book.location
+            );
+            return Ok(());
         }
+    };
+
+    // the thumbnail is shown, the link opens the large one
+    if let Some(img) = profile.book.cover_small.first(div_mainpic)? {
+        book.cover_url_small = img.value().attr("src").unwrap_or_default().to_owned();
+    }
+    if let Some(a_nbg) = profile.book.cover_large.first(div_mainpic)? {
+        book.cover_url_large = a_nbg.value().attr("href").unwrap_or_default().to_owned();
     }
+    // books without a cover link to nothing, or to the thumbnail
+    if book.cover_url_large.is_empty() {
+        book.cover_url_large = book.cover_url_small.clone();
+    }
+    trace!(
+        "cover parsed, small= {:?}, large= {:?}",
+        book.cover_url_small,
+        book.cover_url_large
+    );
 
     Ok(())
 }
 
-fn parse_basic_info(document: &Html, book: &mut Book) -> anyhow::Result<()> {
-    let div_info_selector = get_selector(r#"div[id="info"]"#)?;
-    if let Some(div_info) = document.select(&div_info_selector).next() {
+fn parse_basic_info(document: &Html, book: &mut Book) -> Result<(), ParseError> {
+    let profile = crate::profile::get();
+    if let Some(div_info) = profile.book.info.first(document.root_element())? {
         trace!("div_info is not none");
-        let span_p1_selector = get_selector(r#"span[class="pl"]"#)?;
-        let span_p1_iter = div_info.select(&span_p1_selector);
-        for span_p1 in span_p1_iter {
+        for span_p1 in profile.book.info_label.all(div_info)? {
             let text = span_p1.text().collect::<Vec<_>>();
             let info_type = if !text.is_empty() {
                 clean_basic_info_type(text[0])
@@ -85,8 +151,8 @@ fn parse_basic_info(document: &Html, book: &mut Book) -> anyhow::Result<()> {
                 continue;
             };
 
-            let mut info_a_texts: Vec<String> = Vec::new();
-            let mut info_text_texts: Vec<String> = Vec::new();
+            let mut info_a_texts: Vec<InfoValue> = Vec::new();
+            let mut info_text_texts: Vec<InfoValue> = Vec::new();
             let siblings = span_p1.next_siblings();
             for sibling in siblings {
                 if parse_basic_info_text(sibling, &mut info_a_texts, &mut info_text_texts) {
@@ -101,10 +167,17 @@ fn parse_basic_info(document: &Html, book: &mut Book) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// A piece of a basic info value, with the link it points to if it is an `<a>`.
+#[derive(Debug)]
+struct InfoValue {
+    text: String,
+    href: Option<String>,
+}
+
 fn parse_basic_info_text(
     node_ref: NodeRef<Node>,
-    info_a_texts: &mut Vec<String>,
-    info_text_texts: &mut Vec<String>,
+    info_a_texts: &mut Vec<InfoValue>,
+    info_text_texts: &mut Vec<InfoValue>,
 ) -> bool {
     trace!(
         "parse basic info, info_a_texts= {:?}, info_text_texts= {:?}",
@@ -124,7 +197,10 @@ fn parse_basic_info_text(
                 if !texts.is_empty() {
                     let info_text = clean_basic_info_text(texts[0].as_str());
                     if !info_text.is_empty() {
-                        info_a_texts.push(info_text);
+                        info_a_texts.push(InfoValue {
+                            text: info_text,
+                            href: element.attr("href").map(|v| v.to_owned()),
+                        });
                     }
                 }
             }
@@ -133,7 +209,10 @@ fn parse_basic_info_text(
         Node::Text(text) => {
             let info_text = clean_basic_info_text(format!("{:?}", text).as_str());
             if !info_text.is_empty() {
-                info_text_texts.push(info_text);
+                info_text_texts.push(InfoValue {
+                    text: info_text,
+                    href: None,
+                });
             }
         }
         _ => (),
@@ -145,8 +224,8 @@ fn parse_basic_info_text(
 fn fill_basic_info_value(
     book: &mut Book,
     info_type: &str,
-    mut info_text_texts: Vec<String>,
-    mut info_a_texts: Vec<String>,
+    mut info_text_texts: Vec<InfoValue>,
+    mut info_a_texts: Vec<InfoValue>,
 ) {
     debug!(
         "fill basic info, info_type= {:?} info_a_texts= {:?}, info_text_texts= {:?}",
@@ -164,58 +243,112 @@ fn fill_basic_info_value(
         return;
     }
 
-    let single_info_value = info_texts[0].clone();
-    match info_type {
-        "原作名" => {
+    let single_info_value = info_texts[0].text.clone();
+    let single_info_href = info_texts[0].href.clone().unwrap_or_default();
+    let info_field = crate::profile::get().book.labels.get(info_type).copied();
+    match info_field {
+        Some(InfoField::OriginTitle) => {
             book.origin_title = single_info_value;
         }
-        "副标题" => {
+        Some(InfoField::Subtitle) => {
             book.subtitle = single_info_value;
         }
-        "作者" => {
-            for text in info_texts {
-                book.author.push(text.replace(" ", ""));
-            }
+        Some(InfoField::Author) => {
+            book.author
+                .extend(parse_contributors(info_texts, ContributorRole::Author));
         }
-        "译者" => {
-            for text in info_texts {
-                book.translator.push(text.replace(" ", ""));
-            }
+        Some(InfoField::Translator) => {
+            book.translator
+                .extend(parse_contributors(info_texts, ContributorRole::Translator));
         }
-        "出版社" => {
+        Some(InfoField::Press) => {
+            book.press_id = parse_href_id(single_info_href.as_str(), "press");
             book.press = single_info_value;
         }
-        "出品方" => {
+        Some(InfoField::Producer) => {
+            book.producer_id = parse_href_id(single_info_href.as_str(), "producers");
             book.producer = single_info_value;
         }
-        "出版年" => {
+        Some(InfoField::PublicationYear) => {
+            book.publication_date = parse_partial_date(single_info_value.as_str());
+            if book.publication_date.is_none() {
+                warn!(
+                    "failed to parse publication date, text= {:?}, url= {:?}",
+                    single_info_value, book.location
+                );
+            }
             book.publication_year = single_info_value;
         }
-        "页数" => {
+        Some(InfoField::PageNum) => {
+            book.page_count = parse_page_count(single_info_value.as_str());
+            if book.page_count.is_none() {
+                warn!(
+                    "failed to parse page count, text= {:?}, url= {:?}",
+                    single_info_value, book.location
+                );
+            }
             book.page_num = single_info_value;
         }
-        "定价" => {
+        Some(InfoField::Price) => {
+            book.price_value = parse_price(single_info_value.as_str());
+            if book.price_value.is_none() {
+                warn!(
+                    "failed to parse price, text= {:?}, url= {:?}",
+                    single_info_value, book.location
+                );
+            }
             book.price = single_info_value;
         }
-        "装帧" => {
+        Some(InfoField::Binding) => {
             book.binding = single_info_value;
         }
-        "丛书" => {
+        Some(InfoField::Series) => {
+            book.series_id = parse_href_id(single_info_href.as_str(), "series");
             book.series = single_info_value;
         }
-        "isbn" | "ISBN" => {
-            book.isbn = single_info_value;
+        Some(InfoField::Isbn) => {
+            fill_isbn(book, single_info_value);
         }
-        "统一书号" => {
+        Some(InfoField::UnifiedBookNumber) => {
             book.unified_book_number = single_info_value;
         }
-        _ => {
+        None => {
             warn!(
                 "unexpected info_type, info_type= {:?}, url= {:?}",
                 info_type, book.location
             );
+            book.unknown_info
+                .push((info_type.to_owned(), single_info_value));
+        }
+    }
+}
+
+fn parse_contributors(info_texts: Vec<InfoValue>, role: ContributorRole) -> Vec<Contributor> {
+    info_texts
+        .into_iter()
+        .filter_map(|v| parse_contributor(v.text.as_str(), v.href.as_deref(), role))
+        .collect()
+}
+
+fn fill_isbn(book: &mut Book, isbn: String) {
+    match crate::isbn::parse(isbn.as_str()) {
+        Some((isbn13, isbn10)) => {
+            book.isbn13 = Some(isbn13);
+            book.isbn10 = isbn10;
+            book.isbn_status = IsbnStatus::Valid;
+        }
+        None => {
+            // older books carry their 统一书号 in the isbn field
+            if crate::isbn::looks_like_unified_book_number(isbn.as_str())
+                && book.unified_book_number.is_empty()
+            {
+                book.unified_book_number = isbn.clone();
+            }
+            book.isbn_status = IsbnStatus::Invalid;
+            warn!("invalid isbn, isbn= {:?}, url= {:?}", isbn, book.location);
         }
     }
+    book.isbn = isbn;
 }
 
 fn clean_basic_info_type(info_type: &str) -> String {
@@ -240,18 +373,49 @@ fn clean_basic_info_text(info_text: &str) -> String {
     info_text.trim_matches(TRIM_MATCH_LIST).trim().to_owned()
 }
 
-fn parse_score(document: &Html, book: &mut Book) -> anyhow::Result<()> {
+fn parse_score(document: &Html, book: &mut Book) -> Result<(), ParseError> {
     let mut score = Score::default();
-    let div_rating_wrap_selector = get_selector(r#"div[class="rating_wrap clearbox"]"#)?;
-    if let Some(div_rating_wrap) = document.select(&div_rating_wrap_selector).next() {
+    let profile = crate::profile::get();
+    if let Some(div_rating_wrap) = profile.book.rating_wrap.first(document.root_element())? {
+        // nothing else to parse when there is no score to show
+        let rating_wrap_text = div_rating_wrap.text().collect::<String>();
+        if profile
+            .book
+            .texts
+            .insufficient_ratings
+            .matches(&rating_wrap_text)
+        {
+            score.state = RatingState::Insufficient;
+        } else if profile.book.texts.no_ratings.matches(&rating_wrap_text) {
+            score.state = RatingState::NoRatings;
+        }
+        if score.state != RatingState::ParseFailed {
+            debug!(
+                "book has no score, state= {:?}, url= {:?}",
+                score.state, book.location
+            );
+            book.score = score;
+            return Ok(());
+        }
+
         // rating_num
-        parse_score_rating_num(div_rating_wrap, &mut score, &book)?;
+        parse_score_rating_num(div_rating_wrap, &mut score, book)?;
 
         // rating_people
-        parse_score_rating_people(div_rating_wrap, &mut score, &book)?;
+        parse_score_rating_people(div_rating_wrap, &mut score, book)?;
 
         // star percent
-        parse_score_star_percent(div_rating_wrap, &mut score, &book)?;
+        parse_score_star_percent(div_rating_wrap, &mut score, book)?;
+
+        // the percentages are not worth much without the score itself
+        if score.score.is_some() && score.score_num.is_some() {
+            score.state = RatingState::Rated;
+        }
+    } else {
+        warn!(
+            "parse score error, div_rating_wrap is empty, url= {:?}",
+            book.location
+        );
     }
     book.score = score;
 
@@ -262,9 +426,12 @@ fn parse_score_rating_num(
     div_rating_wrap: ElementRef,
     score: &mut Score,
     book: &Book,
-) -> anyhow::Result<()> {
-    let strong_rating_num_selector = get_selector(r#"strong[class="ll rating_num "]"#)?;
-    match div_rating_wrap.select(&strong_rating_num_selector).next() {
+) -> Result<(), ParseError> {
+    match crate::profile::get()
+        .book
+        .rating_num
+        .first(div_rating_wrap)?
+    {
         Some(strong_rating_num) => {
             let text = strong_rating_num.text().collect::<Vec<_>>();
             if text.is_empty() {
@@ -275,7 +442,7 @@ fn parse_score_rating_num(
             } else {
                 let rating_num = text[0].trim().parse::<f32>();
                 match rating_num {
-                    Ok(rating_num) => score.score = rating_num,
+                    Ok(rating_num) => score.score = Some(rating_num),
                     Err(e) => warn!(
                         "parse score error, parse rating_num to f32 fail, e= {:?}, url= {:?}",
                         e, book.location
@@ -296,9 +463,12 @@ fn parse_score_rating_people(
     div_rating_wrap: ElementRef,
     score: &mut Score,
     book: &Book,
-) -> anyhow::Result<()> {
-    let a_rating_people_selector = get_selector(r#"a[class="rating_people"]"#)?;
-    match div_rating_wrap.select(&a_rating_people_selector).next() {
+) -> Result<(), ParseError> {
+    match crate::profile::get()
+        .book
+        .rating_people
+        .first(div_rating_wrap)?
+    {
         Some(a_rating_people) => {
             let text = a_rating_people.text().collect::<Vec<_>>();
             if text.is_empty() {
@@ -309,7 +479,7 @@ fn parse_score_rating_people(
             } else {
                 let rating_people = text[0].trim().parse::<i32>();
                 match rating_people {
-                    Ok(rating_people) => score.score_num = rating_people,
+                    Ok(rating_people) => score.score_num = Some(rating_people),
                     Err(e) => warn!(
                         "parse score error, parse rating_people to i32 fail, e= {:?}, url= {:?}",
                         e, book.location
@@ -330,9 +500,12 @@ fn parse_score_star_percent(
     div_rating_wrap: ElementRef,
     score: &mut Score,
     book: &Book,
-) -> anyhow::Result<()> {
-    let div_rating_self_selector = get_selector(r#"div[class="rating_self clearfix"]"#)?;
-    match div_rating_wrap.select(&div_rating_self_selector).next() {
+) -> Result<(), ParseError> {
+    match crate::profile::get()
+        .book
+        .rating_self
+        .first(div_rating_wrap)?
+    {
         Some(div_rating_self) => {
             parse_score_star_percent_core(div_rating_self, score, book);
         }
@@ -406,73 +579,321 @@ fn parse_score_star_percent_core(div_rating_self: ElementRef, score: &mut Score,
     }
 }
 
-fn parse_related_info(document: &Html, book: &mut Book) -> anyhow::Result<()> {
-    let div_related_info_selector = get_selector(r#"div[class="related_info"]"#)?;
-    let div_link_report_selector = get_selector(r#"div[id="link-report"]"#)?;
-    let div_intro_selector = get_selector(r#"div[class="intro"]"#)?;
-    if let Some(div_related_info) = document.select(&div_related_info_selector).next() {
-        let mut traced_div_intro_count = 0;
-        // content intro
-        if let Some(div_link_report) = div_related_info.select(&div_link_report_selector).next() {
-            let div_intro_iter = div_link_report.select(&div_intro_selector);
-            for div_intro in div_intro_iter {
-                traced_div_intro_count += 1;
-                book.content_intro = clean_related_info_text(Box::new(div_intro.text()), false);
-            }
+fn parse_collector(document: &Html, book: &mut Book) -> Result<(), ParseError> {
+    let profile = crate::profile::get();
+    let texts = &profile.book.texts;
+    let div_collector = match profile.book.collector.first(document.root_element())? {
+        Some(v) => v,
+        None => {
+            warn!(
+                "parse collector error, div_collector is empty, url= {:?}",
+                book.location
+            );
+            return Ok(());
         }
+    };
+
+    // "1234人在读", "5678人读过", "9012人想读", linking to doings, collections and wishes
+    for a in profile.book.collector_link.all(div_collector)? {
+        let text = a.text().collect::<String>();
+        let count = parse_count(text.as_str());
+        if texts.wish.matches(&text) {
+            book.wish_count = count;
+        } else if texts.reading.matches(&text) {
+            book.reading_count = count;
+        } else if texts.read.matches(&text) {
+            book.read_count = count;
+        } else {
+            trace!(
+                "parse collector, unknown link, text= {:?}, url= {:?}",
+                text.trim(),
+                book.location
+            );
+        }
+    }
 
-        // author intro
-        let div_intro_iter = div_related_info.select(&div_intro_selector);
-        for div_intro in div_intro_iter {
-            if traced_div_intro_count != 0 {
-                traced_div_intro_count -= 1;
-                continue;
+    Ok(())
+}
+
+fn parse_related_info(document: &Html, book: &mut Book) -> Result<(), ParseError> {
+    let profile = crate::profile::get();
+    let texts = &profile.book.texts;
+    let div_related_info = match profile.book.related_info.first(document.root_element())? {
+        Some(v) => v,
+        None => {
+            warn!(
+                "parse related info error, div_related_info is empty, url= {:?}",
+                book.location
+            );
+            return Ok(());
+        }
+    };
+
+    // every section is an h2 heading followed by its div
+    let mut heading = String::new();
+    let mut content_intro_div = None;
+    let mut author_intro_div = None;
+    let mut directory_div = None;
+    for child in div_related_info.children().filter_map(ElementRef::wrap) {
+        match child.value().name() {
+            "h2" => heading = child.text().collect::<String>(),
+            "div" if texts.content_intro_heading.matches(&heading) => {
+                content_intro_div = Some(child)
+            }
+            "div" if texts.author_intro_heading.matches(&heading) => author_intro_div = Some(child),
+            "div" if texts.directory_heading.matches(&heading) && directory_div.is_none() => {
+                directory_div = Some(child)
             }
-            book.author_intro = clean_related_info_text(Box::new(div_intro.text()), false);
+            _ => (),
+        }
+    }
+    // older pages have no heading before the content intro
+    let content_intro_div = match content_intro_div {
+        Some(v) => Some(v),
+        None => profile.book.content_intro.first(div_related_info)?,
+    };
+
+    if let Some(div) = content_intro_div {
+        book.content_intro = parse_intro(div, &profile.book.intro)?;
+    }
+    if let Some(div) = author_intro_div {
+        book.author_intro = parse_intro(div, &profile.book.intro)?;
+    }
+
+    // directory, the full one is hidden next to the folded one
+    let div_dir = profile
+        .book
+        .directory_full
+        .first(div_related_info)?
+        .or(directory_div);
+    if let Some(div_dir) = div_dir {
+        let lines = block_text(div_dir)
+            .lines()
+            .filter(|line| !is_directory_toggle(line))
+            .map(|line| line.to_owned())
+            .collect::<Vec<_>>();
+        book.directory = parse_directory(lines.as_slice());
+    }
+
+    Ok(())
+}
+
+/// The full intro when the page folds it behind "展开全部", paragraphs separated by an empty line.
+fn parse_intro(div: ElementRef, intro: &SelectorChain) -> Result<String, ParseError> {
+    let div_intro = match intro.first(div)? {
+        Some(v) => v,
+        None => return Ok(String::new()),
+    };
+
+    let mut paragraphs: Vec<String> = crate::profile::get()
+        .book
+        .intro_paragraph
+        .all(div_intro)?
+        .into_iter()
+        .map(block_text)
+        .collect();
+    if paragraphs.is_empty() {
+        paragraphs.push(block_text(div_intro));
+    }
+
+    Ok(paragraphs
+        .iter()
+        .map(|p| {
+            p.lines()
+                .map(|line| line.trim())
+                .filter(|line| !line.is_empty())
+                .collect::<Vec<_>>()
+                .join("\n")
+        })
+        .filter(|p| !p.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n"))
+}
+
+/// The text of the element with each `<br>` as a line break, the markup is dropped.
+fn block_text(element: ElementRef) -> String {
+    let mut text = String::new();
+    for node in element.descendants() {
+        match node.value() {
+            Node::Text(t) => text.push_str(t),
+            Node::Element(e) if e.name() == "br" => text.push('\n'),
+            _ => (),
         }
+    }
+    text
+}
+
+/// The "· · · · · · (收起)" line closing a directory, "(更多)" on the folded one.
+fn is_directory_toggle(line: &str) -> bool {
+    const DIR_TRIM_MATCH_LIST: &[char] = &['(', ')', '·', '（', '）'];
+    let texts = &crate::profile::get().book.texts;
+    let rest = line
+        .trim()
+        .trim_matches(|c: char| c.is_whitespace() || DIR_TRIM_MATCH_LIST.contains(&c));
+    rest.is_empty() || texts.directory_toggle.is(rest)
+}
 
-        // directory
-        let book_id = crate::utils::parse_book_id(book.location.as_str());
-        if book_id.is_empty() {
+fn parse_tags(document: &Html, book: &mut Book) -> Result<(), ParseError> {
+    let profile = crate::profile::get();
+    let div_tags_section = match profile.book.tags_section.first(document.root_element())? {
+        Some(v) => v,
+        None => {
             warn!(
-                "parse related info, book_id is empty, book_page_url= {:?}",
+                "parse tags error, div_tags_section is empty, url= {:?}",
                 book.location
             );
+            return Ok(());
         }
-        let div_dir_id_full_selector =
-            get_selector(format!("div[id=\"dir_{}_full\"]", book_id).as_str())?;
-        if let Some(div_dir_id_full) = div_related_info.select(&div_dir_id_full_selector).next() {
-            book.directory = clean_related_info_text(Box::new(div_dir_id_full.text()), true);
+    };
+
+    for a_tag in profile.book.tag.all(div_tags_section)? {
+        let name = a_tag.text().collect::<String>().trim().to_owned();
+        if name.is_empty() {
+            continue;
         }
+
+        // older pages put the count right after the link, "小说(12345)"
+        let count = a_tag
+            .next_sibling()
+            .and_then(|sibling| match sibling.value() {
+                Node::Text(text) => Some(text.to_string()),
+                _ => None,
+            })
+            .and_then(|text| {
+                let text = text.trim().trim_start_matches(&['(', '（'][..]);
+                let end = text.find(&[')', '）'][..])?;
+                text[..end].trim().parse::<u32>().ok()
+            });
+        book.tags.push(Tag { name, count });
     }
 
     Ok(())
 }
 
-fn clean_related_info_text<'a>(texts: Box<dyn Iterator<Item = &str> + 'a>, is_dir: bool) -> String {
-    const DIR_TRIM_MATCH_LIST: &[char] = &['\n', '\t', ' ', '(', ')', '·'];
-    const DIR_IGNORE_STR: &str = "收起";
-    let final_intro = String::new();
-    texts
-        .filter_map(|s| {
-            let mut s = s.trim();
-            if is_dir {
-                s = s.trim_matches(DIR_TRIM_MATCH_LIST);
-                if s == DIR_IGNORE_STR {
-                    return None;
-                }
-            }
-            if s.is_empty() {
-                None
-            } else {
-                Some(s)
-            }
-        })
-        .fold(final_intro, |mut final_intro, s| {
-            if !final_intro.is_empty() {
-                final_intro.push('\n');
+/// Subjects the links point to, deduplicated, without the book itself.
+fn collect_subject_refs<'a>(
+    links: impl Iterator<Item = ElementRef<'a>>,
+    book: &Book,
+) -> Vec<SubjectRef> {
+    let book_id = crate::utils::parse_book_id(book.location.as_str());
+    let mut subject_refs: Vec<SubjectRef> = Vec::new();
+    for a in links {
+        let id = match parse_href_id(a.value().attr("href").unwrap_or_default(), "subject") {
+            Some(id) => id,
+            None => continue,
+        };
+        // the cover and the title often link to the same subject
+        if id == book_id || subject_refs.iter().any(|r| r.id == id) {
+            continue;
+        }
+        let title = a
+            .value()
+            .attr("title")
+            .map(|v| v.to_owned())
+            .unwrap_or_else(|| a.text().collect::<String>())
+            .trim()
+            .to_owned();
+        subject_refs.push(SubjectRef { id, title });
+    }
+
+    subject_refs
+}
+
+fn parse_related_subjects(document: &Html, book: &mut Book) -> Result<(), ParseError> {
+    let profile = crate::profile::get();
+    let root = document.root_element();
+
+    if let Some(div_version_works) = profile.book.other_editions.first(root)? {
+        let links = profile.book.subject_link.all(div_version_works)?;
+        book.other_editions = collect_subject_refs(links.into_iter(), book);
+    }
+
+    // the 丛书信息 block has no id, only its heading tells it apart
+    for div in profile.book.series_block.all(root)? {
+        let is_series = match profile.book.series_heading.first(div)? {
+            Some(h2) => profile
+                .book
+                .texts
+                .series_heading
+                .matches(&h2.text().collect::<String>()),
+            None => false,
+        };
+        if is_series {
+            let links = profile.book.subject_link.all(div)?;
+            book.series_volumes = collect_subject_refs(links.into_iter(), book);
+            break;
+        }
+    }
+
+    // the cover and the title of each recommendation link to it, the title has the text
+    if let Some(div_rec_section) = profile.book.recommendations.first(root)? {
+        let links = profile.book.recommendation_link.all(div_rec_section)?;
+        book.also_liked = collect_subject_refs(links.into_iter(), book);
+    }
+    trace!(
+        "related subjects parsed, editions= {:?}, series= {:?}, also_liked= {:?}, url= {:?}",
+        book.other_editions.len(),
+        book.series_volumes.len(),
+        book.also_liked.len(),
+        book.location
+    );
+
+    Ok(())
+}
+
+fn parse_reviews(document: &Html, book: &mut Book) -> Result<(), ParseError> {
+    let profile = crate::profile::get();
+
+    for div_review_item in profile.book.review_item.all(document.root_element())? {
+        let a_title = match profile.book.review_title.first(div_review_item)? {
+            Some(v) => v,
+            None => {
+                warn!(
+                    "parse reviews error, review title is empty, url= {:?}",
+                    book.location
+                );
+                continue;
             }
-            final_intro.push_str(s);
-            final_intro
-        })
+        };
+        let url = a_title.value().attr("href").unwrap_or_default().to_owned();
+        let author = profile
+            .book
+            .review_author
+            .first(div_review_item)?
+            .map(|a| a.text().collect::<String>().trim().to_owned())
+            .unwrap_or_default();
+        let rating = profile
+            .book
+            .review_rating
+            .all(div_review_item)?
+            .into_iter()
+            .find_map(|span| parse_star_rating(span.value().attr("class").unwrap_or_default()));
+        // the teaser ends with a "(展开)" link that unfolds the full review in place
+        let teaser = profile
+            .book
+            .review_teaser
+            .first(div_review_item)?
+            .map(|div| {
+                profile
+                    .book
+                    .texts
+                    .review_unfold
+                    .remove_from(&div.text().collect::<String>())
+            })
+            .unwrap_or_default()
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        book.reviews.push(ReviewTeaser {
+            id: parse_href_id(url.as_str(), "review").unwrap_or_default(),
+            title: a_title.text().collect::<String>().trim().to_owned(),
+            url,
+            author,
+            rating,
+            teaser,
+        });
+    }
+
+    Ok(())
 }
@@ -0,0 +1,121 @@
+use crate::book::Comment;
+use crate::error::{Error, ParseError};
+use crate::fetch::get_page;
+use crate::parser::value::{parse_count, parse_partial_date, parse_star_rating};
+use log::{trace, warn};
+use scraper::element_ref::ElementRef;
+use scraper::Html;
+
+pub(crate) const COMMENTS_PER_PAGE: usize = 20;
+
+/// The `page_idx`th page of the full 短评 listing of the book, newest score first.
+pub(crate) fn comments_page_url(book_url: &str, page_idx: usize) -> String {
+    format!(
+        "{}/comments/?start={}&limit={}&status=P&sort=new_score",
+        book_url.trim_end_matches('/'),
+        page_idx * COMMENTS_PER_PAGE,
+        COMMENTS_PER_PAGE
+    )
+}
+
+pub(crate) fn get_and_parse_comments_page(
+    comments_page_url: &str,
+    referrer: &str,
+) -> Result<Vec<Comment>, Error> {
+    let resp_text = get_page(comments_page_url, referrer)?;
+    let document = Html::parse_document(resp_text.as_str());
+
+    Ok(parse_comment_items(
+        document.root_element(),
+        comments_page_url,
+    )?)
+}
+
+/// Every comment item under the element, the book page and the listing share the markup.
+pub(crate) fn parse_comment_items(
+    element: ElementRef,
+    url: &str,
+) -> Result<Vec<Comment>, ParseError> {
+    let profile = crate::profile::get();
+
+    let mut comments = Vec::new();
+    for li in profile.comments.item.all(element)? {
+        let mut comment = Comment {
+            id: li.value().attr("data-cid").unwrap_or_default().to_owned(),
+            user: String::new(),
+            user_url: String::new(),
+            rating: None,
+            date: None,
+            votes: 0,
+            text: String::new(),
+        };
+
+        if let Some(span_vote_count) = profile.comments.vote_count.first(li)? {
+            comment.votes = parse_count(span_vote_count.text().collect::<String>().as_str())
+                .unwrap_or_default();
+        }
+
+        let span_comment_info = match profile.comments.info.first(li)? {
+            Some(v) => v,
+            None => {
+                warn!(
+                    "parse comment error, span_comment_info is empty, url= {:?}",
+                    url
+                );
+                continue;
+            }
+        };
+        if let Some(a) = profile.comments.user.first(span_comment_info)? {
+            comment.user = a.text().collect::<String>().trim().to_owned();
+            comment.user_url = a.value().attr("href").unwrap_or_default().to_owned();
+        }
+        let info_parts = profile.comments.info_part.all(span_comment_info)?;
+        comment.rating = info_parts
+            .iter()
+            .find_map(|span| parse_star_rating(span.value().attr("class").unwrap_or_default()));
+        // newer pages mark the date, older ones only put it in the last span
+        comment.date = profile
+            .comments
+            .time
+            .all(span_comment_info)?
+            .into_iter()
+            .chain(info_parts)
+            .map(|e| e.text().collect::<String>())
+            .find(|text| text.trim().starts_with(|c: char| c.is_ascii_digit()))
+            .and_then(|text| parse_partial_date(text.as_str()));
+
+        comment.text = profile
+            .comments
+            .text
+            .first(li)?
+            .map(|e| e.text().collect::<String>().trim().to_owned())
+            .unwrap_or_default();
+        trace!("comment parsed, comment= {:?}, url= {:?}", comment, url);
+        comments.push(comment);
+    }
+
+    Ok(comments)
+}
+
+/// "全部 N 条" next to the 短评 heading.
+pub(crate) fn parse_comment_count(document: &Html) -> Result<Option<u32>, ParseError> {
+    let profile = crate::profile::get();
+    let comments = &profile.comments;
+
+    let h2 = comments
+        .section_heading
+        .all(document.root_element())?
+        .into_iter()
+        .find(|h2| h2.text().any(|text| comments.texts.heading.matches(text)));
+    let h2 = match h2 {
+        Some(v) => v,
+        None => return Ok(None),
+    };
+    Ok(comments
+        .total_link
+        .all(h2)?
+        .into_iter()
+        .map(|a| a.text().collect::<String>())
+        .find(|text| comments.texts.total.matches(text))
+        .and_then(|text| parse_count(text.as_str())))
+}
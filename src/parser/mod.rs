@@ -1,3 +1,6 @@
 pub(crate) mod book_page;
+pub(crate) mod comments_page;
 pub(crate) mod root_page;
+pub(crate) mod search_page;
 pub(crate) mod tag_page;
+pub(crate) mod value;
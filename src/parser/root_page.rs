@@ -1,21 +1,73 @@
+use crate::book::{TagCategory, TagEntry};
+use crate::error::{Error, ParseError};
 use crate::fetch::get_page;
-use crate::utils::get_selector;
+use crate::parser::value::parse_count;
+use log::warn;
+use scraper::element_ref::ElementRef;
 use scraper::Html;
 
-pub(crate) fn get_and_parse_root_page() -> anyhow::Result<Vec<String>> {
-    let root_url = "https://book.douban.com/tag/";
-    let resp_text = get_page(root_url, root_url)?;
-    let document = Html::parse_document(resp_text.as_str());
-    let table_selector = get_selector(r#"table[class="tagCol"]"#)?;
-    let a_selector = get_selector("a")?;
+pub(crate) const ROOT_URL: &str = "https://book.douban.com/tag/";
 
-    let mut tags_href = Vec::new();
-    for table in document.select(&table_selector) {
-        for a in table.select(&a_selector) {
-            if let Some(href) = a.value().attr("href") {
-                tags_href.push(String::from(href));
-            }
+pub(crate) fn get_and_parse_root_page() -> Result<Vec<TagCategory>, Error> {
+    let resp_text = get_page(ROOT_URL, ROOT_URL)?;
+    parse_root_document(resp_text.as_str())
+}
+
+pub(crate) fn parse_root_document(html: &str) -> Result<Vec<TagCategory>, Error> {
+    let document = Html::parse_document(html);
+    let profile = crate::profile::get();
+
+    let mut categories: Vec<TagCategory> = Vec::new();
+    for table in profile.root.tag_table.all(document.root_element())? {
+        let name = parse_category_name(table)?;
+        let mut tags = Vec::new();
+        for a in profile.root.tag_link.all(table)? {
+            let href = match a.value().attr("href") {
+                Some(v) => v.to_owned(),
+                None => continue,
+            };
+            // the count is next to the link, in the same cell
+            let count = match a.parent().and_then(ElementRef::wrap) {
+                Some(td) => profile
+                    .root
+                    .tag_count
+                    .first(td)?
+                    .and_then(|b| parse_count(b.text().collect::<String>().as_str())),
+                None => None,
+            };
+            tags.push(TagEntry {
+                name: a.text().collect::<String>().trim().to_owned(),
+                href,
+                count,
+            });
+        }
+
+        // a heading split over several tables is still one category
+        match categories.iter_mut().find(|c| c.name == name) {
+            Some(category) => category.tags.extend(tags),
+            None => categories.push(TagCategory { name, tags }),
         }
     }
-    Ok(tags_href)
+    Ok(categories)
+}
+
+/// The heading right before the table, "文学 · · · · · · " gives "文学".
+fn parse_category_name(table: ElementRef) -> Result<String, ParseError> {
+    const TITLE_TRIM_MATCH_LIST: &[char] = &['·', ' ', '\u{a0}'];
+    let heading = match table.prev_siblings().find_map(ElementRef::wrap) {
+        Some(v) => v,
+        None => {
+            warn!("parse root page error, tag table has no heading");
+            return Ok(String::new());
+        }
+    };
+
+    let title = match crate::profile::get().root.category_title.first(heading)? {
+        Some(title) => title.text().collect::<String>(),
+        None => heading.value().attr("name").unwrap_or_default().to_owned(),
+    };
+    Ok(title
+        .trim()
+        .trim_matches(|c: char| c.is_whitespace() || TITLE_TRIM_MATCH_LIST.contains(&c))
+        .to_owned())
 }
@@ -0,0 +1,154 @@
+use crate::book::{ListingItem, RatingState};
+use crate::error::{Error, ParseError};
+use crate::fetch::get_page;
+use crate::utils::parse_href_id;
+use log::debug;
+use reqwest::Url;
+use serde::Deserialize;
+
+const SEARCH_URL: &str = "https://search.douban.com/book/subject_search";
+pub(crate) const SEARCH_REFERRER: &str = "https://book.douban.com/";
+// "图书" among the search categories
+const BOOK_CATEGORY: &str = "1001";
+pub(crate) const COUNT_PER_SEARCH_PAGE: u32 = 15;
+
+/// A page of search results.
+#[derive(Debug)]
+pub(crate) struct SearchPage {
+    /// the books only, the author and series entries mixed in are left out
+    pub(crate) items: Vec<ListingItem>,
+    /// results of the query over all pages
+    pub(crate) total: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchData {
+    #[serde(default)]
+    items: Vec<SearchDataItem>,
+    total: Option<u32>,
+    /// results on the pages before
+    #[serde(default)]
+    start: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchDataItem {
+    #[serde(default)]
+    url: String,
+    #[serde(default)]
+    title: String,
+    /// "[日] 东野圭吾 / 李盈春 / 南海出版公司 / 2014-5 / 39.50元"
+    #[serde(default, rename = "abstract")]
+    abstract_line: String,
+    rating: Option<SearchDataRating>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchDataRating {
+    #[serde(default)]
+    value: f32,
+    #[serde(default)]
+    count: u32,
+    /// "(目前无人评价)", "(少于10人评价)", empty for rated books
+    #[serde(default)]
+    rating_info: String,
+}
+
+/// The search page listing the results from `start` on.
+pub(crate) fn search_page_url(search_text: &str, start: u32) -> String {
+    Url::parse_with_params(
+        SEARCH_URL,
+        &[
+            ("search_text", search_text),
+            ("cat", BOOK_CATEGORY),
+            ("start", start.to_string().as_str()),
+        ],
+    )
+    .expect("search url is valid")
+    .to_string()
+}
+
+pub(crate) fn get_and_parse_search_page(
+    search_page_url: &str,
+    referrer: &str,
+) -> Result<SearchPage, Error> {
+    let resp_text = get_page(search_page_url, referrer)?;
+    parse_search_document(resp_text.as_str(), search_page_url)
+}
+
+pub(crate) fn parse_search_document(
+    html: &str,
+    search_page_url: &str,
+) -> Result<SearchPage, Error> {
+    let data = parse_search_data(html, search_page_url)?;
+
+    let mut items = Vec::new();
+    for item in data.items {
+        let id = match parse_href_id(item.url.as_str(), "subject") {
+            Some(v) => v,
+            None => {
+                debug!(
+                    "not a book, skip search result, url= {:?}, search_page_url= {:?}",
+                    item.url, search_page_url
+                );
+                continue;
+            }
+        };
+
+        let mut listing_item = ListingItem {
+            url: item.url,
+            id,
+            title: item.title.trim().to_owned(),
+            pub_line: item.abstract_line.trim().to_owned(),
+            rating_state: RatingState::ParseFailed,
+            score: None,
+            score_num: None,
+            description: String::new(),
+            // over all pages of the query, the author and series entries left out
+            rank: data.start + items.len() + 1,
+        };
+        if let Some(rating) = item.rating {
+            parse_search_rating(rating, &mut listing_item);
+        }
+        items.push(listing_item);
+    }
+
+    Ok(SearchPage {
+        items,
+        total: data.total,
+    })
+}
+
+fn parse_search_data(html: &str, search_page_url: &str) -> Result<SearchData, ParseError> {
+    let missing = || ParseError::MissingElement {
+        element: "search data",
+        url: search_page_url.to_owned(),
+    };
+    let profile = crate::profile::get();
+    let data_marker = profile.search.data_marker.as_str();
+    let pos = html.find(data_marker).ok_or_else(missing)?;
+    // the object is followed by the rest of the script
+    serde_json::Deserializer::from_str(&html[pos + data_marker.len()..])
+        .into_iter::<SearchData>()
+        .next()
+        .and_then(|data| data.ok())
+        .ok_or_else(missing)
+}
+
+fn parse_search_rating(rating: SearchDataRating, listing_item: &mut ListingItem) {
+    let texts = &crate::profile::get().tag.texts;
+    if texts.insufficient_ratings.matches(&rating.rating_info) {
+        listing_item.rating_state = RatingState::Insufficient;
+        return;
+    }
+    if texts.no_ratings.matches(&rating.rating_info) {
+        listing_item.rating_state = RatingState::NoRatings;
+        return;
+    }
+
+    if 0.0 < rating.value && 0 < rating.count {
+        listing_item.rating_state = RatingState::Rated;
+        listing_item.score = Some(rating.value);
+        listing_item.score_num = Some(rating.count);
+    }
+}
@@ -1,19 +1,47 @@
+use crate::book::{ListingItem, RatingState};
+use crate::error::{Error, ParseError};
 use crate::fetch::get_page;
-use crate::utils::{get_selector, node_ref_text};
-use anyhow::anyhow;
+use crate::parser::value::parse_count;
+use crate::utils::{node_ref_text, parse_href_id};
 use log::{debug, warn};
 use scraper::element_ref::ElementRef;
 use scraper::node::Node;
 use scraper::Html;
-use std::iter::Iterator;
 
-pub(crate) fn get_max_tag_page_count(tag_page_url: &str, referrer: &str) -> anyhow::Result<i32> {
+/// A page of a tag listing.
+#[derive(Debug)]
+pub(crate) struct TagPage {
+    pub(crate) items: Vec<ListingItem>,
+    /// the "后页" link, none on the last page
+    pub(crate) next_url: Option<String>,
+    /// the last page number the paginator shows, none when there is no paginator
+    pub(crate) page_count: Option<i32>,
+}
+
+pub(crate) fn get_and_parse_tag_page(tag_page_url: &str, referrer: &str) -> Result<TagPage, Error> {
     let resp_text = get_page(tag_page_url, referrer)?;
-    let document = Html::parse_document(resp_text.as_str());
+    parse_tag_document(resp_text.as_str(), tag_page_url)
+}
+
+pub(crate) fn parse_tag_document(html: &str, tag_page_url: &str) -> Result<TagPage, Error> {
+    let document = Html::parse_document(html);
 
+    let page_count = match parse_max_tag_page_count(&document, tag_page_url) {
+        Ok(v) => Some(v),
+        Err(ParseError::MissingElement { .. }) => None,
+        Err(e) => return Err(e.into()),
+    };
+    Ok(TagPage {
+        items: parse_listing_items(&document, tag_page_url)?,
+        next_url: parse_next_url(&document, tag_page_url)?,
+        page_count,
+    })
+}
+
+fn parse_max_tag_page_count(document: &Html, tag_page_url: &str) -> Result<i32, ParseError> {
     let mut max_tag_page_count = 0;
-    let div_paginator_selector = get_selector(r#"div[class="paginator"]"#)?;
-    match document.select(&div_paginator_selector).next() {
+    let profile = crate::profile::get();
+    match profile.tag.paginator.first(document.root_element())? {
         Some(div_paginator) => {
             let a_texts = parse_children_a_texts(div_paginator, tag_page_url);
             for text in a_texts {
@@ -27,47 +55,124 @@ pub(crate) fn get_max_tag_page_count(tag_page_url: &str, referrer: &str) -> anyh
             }
             Ok(max_tag_page_count)
         }
-        None => Err(anyhow!(
-            "parse max tag page count error, no paginator found"
-        )),
+        None => Err(ParseError::MissingElement {
+            element: "paginator",
+            url: tag_page_url.to_owned(),
+        }),
+    }
+}
+
+/// The absolute url of the "后页" link.
+fn parse_next_url(document: &Html, tag_page_url: &str) -> Result<Option<String>, ParseError> {
+    let profile = crate::profile::get();
+    let href = match profile.tag.next_link.first(document.root_element())? {
+        Some(a) => a.value().attr("href").unwrap_or_default().trim().to_owned(),
+        None => return Ok(None),
+    };
+    if href.is_empty() {
+        warn!("next link has no href, tag_page_url= {:?}", tag_page_url);
+        return Ok(None);
+    }
+
+    let next_url = crate::utils::absolute_url(tag_page_url, href.as_str());
+    if next_url.is_none() {
+        warn!(
+            "next link is not a valid url, href= {:?}, tag_page_url= {:?}",
+            href, tag_page_url
+        );
     }
+    Ok(next_url)
 }
 
-pub(crate) fn get_and_parse_tag_page(
+fn parse_listing_items(
+    document: &Html,
     tag_page_url: &str,
-    referrer: &str,
-) -> anyhow::Result<Vec<String>> {
-    let resp_text = get_page(tag_page_url, referrer)?;
-    let document = Html::parse_document(resp_text.as_str());
-    let li_selector = get_selector(r#"li[class="subject-item"]"#)?;
-    let h2_selector = get_selector("h2")?;
-    let a_selector = get_selector("a")?;
-
-    let mut books_url = Vec::new();
-    for li in document.select(&li_selector) {
-        if let Some(h2) = li.select(&h2_selector).next() {
-            if let Some(a) = h2.select(&a_selector).next() {
-                let mut url: String = String::new();
-                let mut title: String = String::new();
-                if let Some(href) = a.value().attr("href") {
-                    url = String::from(href);
-                }
-                if let Some(t) = a.value().attr("title") {
-                    title = String::from(t);
-                }
-                debug!(
-                    "parse new book url, title: {:?}, book_url: {:?}",
-                    title, url
-                );
-                if url.is_empty() {
-                    warn!("href not found, tag_page_url= {:?}", tag_page_url);
-                    continue;
-                }
-                books_url.push(url.clone());
-            }
+) -> Result<Vec<ListingItem>, ParseError> {
+    let profile = crate::profile::get();
+
+    let mut listing_items = Vec::new();
+    for (idx, li) in profile
+        .tag
+        .subject_item
+        .all(document.root_element())?
+        .into_iter()
+        .enumerate()
+    {
+        let a = match profile.tag.subject_link.first(li)? {
+            Some(v) => v,
+            None => continue,
+        };
+        let url = a.value().attr("href").unwrap_or_default().to_owned();
+        if url.is_empty() {
+            warn!("href not found, tag_page_url= {:?}", tag_page_url);
+            continue;
         }
+        let title = match a.value().attr("title") {
+            Some(t) => t.to_owned(),
+            None => a.text().collect::<String>(),
+        };
+
+        let mut listing_item = ListingItem {
+            id: parse_href_id(url.as_str(), "subject").unwrap_or_default(),
+            url,
+            title: title.trim().to_owned(),
+            pub_line: element_text(profile.tag.pub_line.first(li)?),
+            rating_state: RatingState::ParseFailed,
+            score: None,
+            score_num: None,
+            description: element_text(profile.tag.description.first(li)?),
+            // the rank the page shows, books without a link included
+            rank: idx + 1,
+        };
+        parse_listing_rating(li, &mut listing_item)?;
+        debug!(
+            "parse new listing item, listing_item= {:?}, tag_page_url= {:?}",
+            listing_item, tag_page_url
+        );
+        listing_items.push(listing_item);
     }
-    Ok(books_url)
+    Ok(listing_items)
+}
+
+fn element_text(element: Option<ElementRef>) -> String {
+    element
+        .map(|e| e.text().collect::<Vec<_>>().join(" "))
+        .unwrap_or_default()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn parse_listing_rating(li: ElementRef, listing_item: &mut ListingItem) -> Result<(), ParseError> {
+    let tag_profile = &crate::profile::get().tag;
+    let rating_people = element_text(tag_profile.rating_people.first(li)?);
+    if tag_profile
+        .texts
+        .insufficient_ratings
+        .matches(&rating_people)
+    {
+        listing_item.rating_state = RatingState::Insufficient;
+        return Ok(());
+    }
+    if tag_profile.texts.no_ratings.matches(&rating_people) {
+        listing_item.rating_state = RatingState::NoRatings;
+        return Ok(());
+    }
+
+    listing_item.score = element_text(tag_profile.rating.first(li)?)
+        .parse::<f32>()
+        .ok();
+    listing_item.score_num = parse_count(rating_people.as_str());
+    if listing_item.score.is_some() && listing_item.score_num.is_some() {
+        listing_item.rating_state = RatingState::Rated;
+    } else {
+        warn!(
+            "parse listing rating error, url= {:?}, rating_people= {:?}",
+            listing_item.url, rating_people
+        );
+    }
+
+    Ok(())
 }
 
 fn parse_children_a_texts(element_ref: ElementRef, tag_page_url: &str) -> Vec<String> {
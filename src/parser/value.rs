@@ -0,0 +1,474 @@
+use crate::book::{Chapter, Contributor, ContributorRole, PartialDate, Price};
+use crate::utils::parse_href_id;
+
+/// Splits the text into its runs of ascii digits, "2005年5月" -> ["2005", "5"].
+fn digit_groups(text: &str) -> Vec<&str> {
+    text.split(|c: char| !c.is_ascii_digit())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// "2005-5", "2005-05-01", "2005年5月", "2005/5/1", "2005.5" ...
+pub(crate) fn parse_partial_date(text: &str) -> Option<PartialDate> {
+    let groups = digit_groups(text);
+    let year = groups.first()?;
+    if year.len() != 4 {
+        return None;
+    }
+    let year = year.parse::<i32>().ok()?;
+
+    let month = groups
+        .get(1)
+        .and_then(|v| v.parse::<u32>().ok())
+        .filter(|v| (1..=12).contains(v));
+    // a day without a month means nothing, "2019-2-31" keeps the month only
+    let day = month.and_then(|month| {
+        groups
+            .get(2)
+            .and_then(|v| v.parse::<u32>().ok())
+            .filter(|v| (1..=days_in_month(year, month)).contains(v))
+    });
+
+    Some(PartialDate { year, month, day })
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// "320", "320页", "320 pages"
+pub(crate) fn parse_page_count(text: &str) -> Option<u32> {
+    digit_groups(text)
+        .first()
+        .and_then(|v| v.parse::<u32>().ok())
+        .filter(|v| *v != 0)
+}
+
+// (marker, currency), longer markers go first so "NT$" is not taken as "$"
+const CURRENCY_MARKERS: &[(&str, &str)] = &[
+    ("NT$", "TWD"),
+    ("TWD", "TWD"),
+    ("新台币", "TWD"),
+    ("HK$", "HKD"),
+    ("HKD", "HKD"),
+    ("港币", "HKD"),
+    ("港元", "HKD"),
+    ("US$", "USD"),
+    ("USD", "USD"),
+    ("美元", "USD"),
+    ("$", "USD"),
+    ("GBP", "GBP"),
+    ("英镑", "GBP"),
+    ("£", "GBP"),
+    ("EUR", "EUR"),
+    ("欧元", "EUR"),
+    ("€", "EUR"),
+    ("JPY", "JPY"),
+    ("日元", "JPY"),
+    ("円", "JPY"),
+    ("CNY", "CNY"),
+    ("RMB", "CNY"),
+    ("人民币", "CNY"),
+    ("元", "CNY"),
+    ("¥", "CNY"),
+    ("￥", "CNY"),
+];
+
+// nearly every price on douban without a currency is in yuan
+const DEFAULT_CURRENCY: &str = "CNY";
+
+/// "CNY 45.00", "29.80元", "$12.99", "45.00"
+pub(crate) fn parse_price(text: &str) -> Option<Price> {
+    let start = text.find(|c: char| c.is_ascii_digit())?;
+    let number: String = text[start..]
+        .chars()
+        .take_while(|c| c.is_ascii_digit() || *c == '.' || *c == ',')
+        .filter(|c| *c != ',')
+        .collect();
+    let amount = number.trim_end_matches('.').parse::<f64>().ok()?;
+
+    let upper = text.to_uppercase();
+    let currency = CURRENCY_MARKERS
+        .iter()
+        .find(|(marker, _)| upper.contains(marker))
+        .map(|(_, currency)| *currency)
+        .unwrap_or(DEFAULT_CURRENCY);
+
+    Some(Price {
+        amount,
+        currency: currency.to_owned(),
+    })
+}
+
+// (open, close) around a nationality or a role, "[美]", "(美)", "【美】", "（编）", "〔美〕"
+const BRACKETS: &[(char, char)] = &[
+    ('[', ']'),
+    ('(', ')'),
+    ('【', '】'),
+    ('（', '）'),
+    ('〔', '〕'),
+];
+
+// (suffix, role), checked in order, longer ones first
+const ROLE_SUFFIXES: &[(&str, ContributorRole)] = &[
+    ("编者", ContributorRole::Editor),
+    ("主编", ContributorRole::Editor),
+    ("编著", ContributorRole::Editor),
+    ("编选", ContributorRole::Editor),
+    ("编", ContributorRole::Editor),
+    ("绘者", ContributorRole::Illustrator),
+    ("绘图", ContributorRole::Illustrator),
+    ("插图", ContributorRole::Illustrator),
+    ("绘", ContributorRole::Illustrator),
+    ("译者", ContributorRole::Translator),
+    ("译", ContributorRole::Translator),
+    ("著", ContributorRole::Author),
+];
+
+fn split_nationality(text: &str) -> (String, &str) {
+    for (open, close) in BRACKETS {
+        if let Some(rest) = text.strip_prefix(*open) {
+            if let Some(pos) = rest.find(*close) {
+                let nationality = rest[..pos].trim();
+                // "[美]" or "[清]", not a whole bracketed name
+                if !nationality.is_empty() && nationality.chars().count() <= 4 {
+                    return (
+                        nationality.to_owned(),
+                        rest[pos + close.len_utf8()..].trim(),
+                    );
+                }
+            }
+        }
+    }
+
+    (String::new(), text)
+}
+
+fn split_role(text: &str) -> (&str, Option<ContributorRole>) {
+    for (suffix, role) in ROLE_SUFFIXES {
+        // "宫崎骏 绘", "张三（编）", "李四 (编者)"
+        for (open, close) in BRACKETS {
+            let wrapped = format!("{}{}{}", open, suffix, close);
+            if let Some(rest) = text.strip_suffix(wrapped.as_str()) {
+                return (rest.trim(), Some(*role));
+            }
+        }
+        if let Some(rest) = text.strip_suffix(suffix) {
+            if rest.ends_with(char::is_whitespace) {
+                return (rest.trim(), Some(*role));
+            }
+        }
+    }
+
+    (text, None)
+}
+
+/// "[美] J. K. 罗琳", "宫崎骏 绘", with its /author/ or /personage/ link if any.
+pub(crate) fn parse_contributor(
+    text: &str,
+    href: Option<&str>,
+    default_role: ContributorRole,
+) -> Option<Contributor> {
+    // keep single spaces, "J. K. Rowling" is not "J.K.Rowling"
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let (nationality, rest) = split_nationality(text.as_str());
+    let (name, role) = split_role(rest);
+    if name.is_empty() {
+        return None;
+    }
+
+    let href = href.unwrap_or_default();
+    Some(Contributor {
+        name: name.to_owned(),
+        nationality,
+        role: role.unwrap_or(default_role),
+        author_id: parse_href_id(href, "author"),
+        personage_id: parse_href_id(href, "personage"),
+    })
+}
+
+/// Stars from a rating class, "allstar40" gives 4, "allstar00" or no such class gives `None`.
+pub(crate) fn parse_star_rating(class: &str) -> Option<u8> {
+    let profile = crate::profile::get();
+    class
+        .split_whitespace()
+        .find_map(|token| token.strip_prefix(profile.star_class_prefix.as_str()))
+        .and_then(|v| v.parse::<u8>().ok())
+        .map(|v| v / 10)
+        .filter(|v| (1..=5).contains(v))
+}
+
+/// The first number in the text, "全部 1234 条" gives 1234, "1,234" is one number.
+pub(crate) fn parse_count(text: &str) -> Option<u32> {
+    let start = text.find(|c: char| c.is_ascii_digit())?;
+    text[start..]
+        .chars()
+        .take_while(|c| c.is_ascii_digit() || *c == ',')
+        .filter(|c| *c != ',')
+        .collect::<String>()
+        .parse::<u32>()
+        .ok()
+}
+
+// leading whitespace width of a directory line, full-width spaces are the usual indent
+fn indent_width(line: &str) -> usize {
+    line.chars()
+        .take_while(|c| c.is_whitespace())
+        .map(|c| match c {
+            '\t' => 4,
+            '\u{3000}' => 2,
+            _ => 1,
+        })
+        .sum()
+}
+
+// "第X<marker>", a larger unit comes first
+const HEADING_MARKERS: &[char] = &['部', '篇', '卷', '编', '章', '节'];
+const CHINESE_NUMERALS: &str = "零〇一二三四五六七八九十百千两";
+
+/// How deep a numbered heading is, "第一部" < "第三章" < "第二节" < "1" < "1.2".
+fn heading_rank(title: &str) -> Option<usize> {
+    if let Some(rest) = title.strip_prefix('第') {
+        let marker = rest
+            .chars()
+            .find(|c| !c.is_ascii_digit() && !CHINESE_NUMERALS.contains(*c))?;
+        return HEADING_MARKERS.iter().position(|m| *m == marker);
+    }
+
+    // "1.2.3 标题", one rank per number
+    let numbering = title.split_whitespace().next()?;
+    let groups: Vec<&str> = numbering.trim_end_matches('.').split('.').collect();
+    if groups
+        .iter()
+        .all(|g| !g.is_empty() && g.chars().all(|c| c.is_ascii_digit()))
+    {
+        return Some(HEADING_MARKERS.len() + groups.len());
+    }
+
+    None
+}
+
+/// The lines of a 目录 as a tree.
+///
+/// Nesting follows the indentation when the lines are indented, and the
+/// numbering of the headings otherwise. Lines it can not place stay at the top level.
+pub(crate) fn parse_directory(lines: &[String]) -> Vec<Chapter> {
+    let lines: Vec<(usize, &str)> = lines
+        .iter()
+        .map(|line| (indent_width(line), line.trim()))
+        .filter(|(_, title)| !title.is_empty())
+        .collect();
+    let indented = lines.iter().any(|(indent, _)| *indent != lines[0].0);
+    let levels: Vec<(Option<usize>, &str)> = lines
+        .into_iter()
+        .map(|(indent, title)| {
+            let level = if indented {
+                Some(indent)
+            } else {
+                heading_rank(title)
+            };
+            (level, title)
+        })
+        .collect();
+
+    let mut chapters: Vec<Chapter> = Vec::new();
+    // levels of the chapters from the top level down to the last one added
+    let mut open_levels: Vec<usize> = Vec::new();
+    for (level, title) in levels {
+        // "序", "附录", nothing goes under them
+        let level = match level {
+            Some(v) => v,
+            None => {
+                open_levels.clear();
+                chapters.push(Chapter {
+                    title: title.to_owned(),
+                    children: Vec::new(),
+                });
+                continue;
+            }
+        };
+        while open_levels.last().map(|l| level <= *l).unwrap_or(false) {
+            open_levels.pop();
+        }
+
+        let mut siblings = &mut chapters;
+        for _ in 0..open_levels.len() {
+            siblings = &mut siblings
+                .last_mut()
+                .expect("an open level always has a chapter")
+                .children;
+        }
+        siblings.push(Chapter {
+            title: title.to_owned(),
+            children: Vec::new(),
+        });
+        open_levels.push(level);
+    }
+
+    chapters
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partial_dates() {
+        let date = |year, month, day| Some(PartialDate { year, month, day });
+        let cases = [
+            ("2014-5", date(2014, Some(5), None)),
+            ("2013-1-1", date(2013, Some(1), Some(1))),
+            ("2019年3月", date(2019, Some(3), None)),
+            ("2005/05/01", date(2005, Some(5), Some(1))),
+            ("2006", date(2006, None, None)),
+            ("2019-2-31", date(2019, Some(2), None)),
+            ("2019-2-29", date(2019, Some(2), None)),
+            ("2020-2-29", date(2020, Some(2), Some(29))),
+            ("2019-4-31", date(2019, Some(4), None)),
+            ("2019-13", date(2019, None, None)),
+            ("05-1", None),
+            ("", None),
+        ];
+        for (text, expected) in cases.iter() {
+            assert_eq!(parse_partial_date(text), *expected, "text= {:?}", text);
+        }
+    }
+
+    #[test]
+    fn contributors() {
+        // (text, nationality, name, role)
+        let cases = [
+            (
+                "[美]  J. K.   罗琳",
+                "美",
+                "J. K. 罗琳",
+                ContributorRole::Author,
+            ),
+            ("【清】曹雪芹 著", "清", "曹雪芹", ContributorRole::Author),
+            (
+                "（日）宫崎骏 绘",
+                "日",
+                "宫崎骏",
+                ContributorRole::Illustrator,
+            ),
+            ("张三（编）", "", "张三", ContributorRole::Editor),
+            ("李四 (编者)", "", "李四", ContributorRole::Editor),
+            ("〔英〕王五 译", "英", "王五", ContributorRole::Translator),
+            // too long for a nationality, and no space before the suffix
+            (
+                "[英国作家协会]赵六编",
+                "",
+                "[英国作家协会]赵六编",
+                ContributorRole::Author,
+            ),
+        ];
+        for (text, nationality, name, role) in cases.iter() {
+            let contributor = parse_contributor(text, None, ContributorRole::Author)
+                .unwrap_or_else(|| panic!("a contributor, text= {:?}", text));
+            assert_eq!(contributor.nationality, *nationality, "text= {:?}", text);
+            assert_eq!(contributor.name, *name, "text= {:?}", text);
+            assert_eq!(contributor.role, *role, "text= {:?}", text);
+        }
+
+        let translator = parse_contributor(
+            "李盈春",
+            Some("https://book.douban.com/author/4526567/"),
+            ContributorRole::Translator,
+        )
+        .expect("a contributor");
+        assert_eq!(translator.role, ContributorRole::Translator);
+        assert_eq!(translator.author_id.as_deref(), Some("4526567"));
+        assert_eq!(translator.personage_id, None);
+        assert!(parse_contributor(" [美] ", None, ContributorRole::Author).is_none());
+    }
+
+    /// "title" per chapter, children indented by two spaces.
+    fn outline(chapters: &[Chapter], depth: usize, out: &mut Vec<String>) {
+        for chapter in chapters {
+            out.push(format!("{}{}", "  ".repeat(depth), chapter.title));
+            outline(&chapter.children, depth + 1, out);
+        }
+    }
+
+    fn parse_outline(lines: &[&str]) -> Vec<String> {
+        let lines: Vec<String> = lines.iter().map(|l| l.to_string()).collect();
+        let mut out = Vec::new();
+        outline(&parse_directory(&lines), 0, &mut out);
+        out
+    }
+
+    #[test]
+    fn directory_by_indentation() {
+        let lines = [
+            "第一部",
+            "\u{3000}\u{3000}第一章 回答是牛奶箱",
+            "\u{3000}\u{3000}第二章 深夜的口琴",
+            "",
+            "第二部",
+            "\u{3000}\u{3000}第三章 在思域车上等到天亮",
+            "\u{3000}\u{3000}\u{3000}\u{3000}一",
+        ];
+        assert_eq!(
+            parse_outline(&lines),
+            vec![
+                "第一部",
+                "  第一章 回答是牛奶箱",
+                "  第二章 深夜的口琴",
+                "第二部",
+                "  第三章 在思域车上等到天亮",
+                "    一",
+            ]
+        );
+    }
+
+    #[test]
+    fn directory_by_numbering() {
+        let lines = [
+            "序",
+            "第一章 基础",
+            "1.1 变量",
+            "1.1.1 作用域",
+            "1.2 函数",
+            "第二章 进阶",
+            "2.1 并发",
+            "附录",
+        ];
+        assert_eq!(
+            parse_outline(&lines),
+            vec![
+                "序",
+                "第一章 基础",
+                "  1.1 变量",
+                "    1.1.1 作用域",
+                "  1.2 函数",
+                "第二章 进阶",
+                "  2.1 并发",
+                "附录",
+            ]
+        );
+    }
+
+    #[test]
+    fn prices() {
+        let cases = [
+            ("39.50元", Some((39.5, "CNY"))),
+            ("USD 12.00", Some((12.0, "USD"))),
+            ("NT$300", Some((300.0, "TWD"))),
+            ("HK$ 98", Some((98.0, "HKD"))),
+            ("$12.99", Some((12.99, "USD"))),
+            ("1,280.00 日元", Some((1280.0, "JPY"))),
+            ("45.00", Some((45.0, "CNY"))),
+            ("免费", None),
+        ];
+        for (text, expected) in cases.iter() {
+            let parsed = parse_price(text).map(|p| (p.amount, p.currency));
+            let expected = expected.map(|(amount, currency)| (amount, currency.to_owned()));
+            assert_eq!(parsed, expected, "text= {:?}", text);
+        }
+    }
+}
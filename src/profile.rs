@@ -0,0 +1,384 @@
+use crate::error::ParseError;
+use crate::utils::get_selector;
+use anyhow::anyhow;
+use lazy_static::lazy_static;
+use log::info;
+use scraper::element_ref::ElementRef;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::sync::{Arc, RwLock};
+
+lazy_static! {
+    static ref PROFILE: RwLock<Arc<Profile>> = RwLock::new(Arc::new(
+        parse(DEFAULT_PROFILE).expect("the built-in profile should be valid")
+    ));
+}
+
+// the format version this binary reads
+const PROFILE_VERSION: u32 = 1;
+const DEFAULT_PROFILE: &str = include_str!("../profiles/douban.toml");
+
+/// CSS selectors tried in order, the first one matching anything wins.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(transparent)]
+pub(crate) struct SelectorChain(Vec<String>);
+
+impl SelectorChain {
+    /// The first element matched under `element`.
+    pub(crate) fn first<'a>(
+        &self,
+        element: ElementRef<'a>,
+    ) -> Result<Option<ElementRef<'a>>, ParseError> {
+        for selector_str in self.0.iter() {
+            let selector = get_selector(selector_str)?;
+            if let Some(v) = element.select(&selector).next() {
+                return Ok(Some(v));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Every element matched by the first selector that matches any.
+    pub(crate) fn all<'a>(
+        &self,
+        element: ElementRef<'a>,
+    ) -> Result<Vec<ElementRef<'a>>, ParseError> {
+        for selector_str in self.0.iter() {
+            let selector = get_selector(selector_str)?;
+            let matched: Vec<ElementRef<'a>> = element.select(&selector).collect();
+            if !matched.is_empty() {
+                return Ok(matched);
+            }
+        }
+        Ok(Vec::new())
+    }
+
+    fn check(&self) -> Result<(), ParseError> {
+        for selector_str in self.0.iter() {
+            get_selector(selector_str)?;
+        }
+        Ok(())
+    }
+}
+
+/// Texts any of which marks something on the page, e.g. a heading.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(transparent)]
+pub(crate) struct TextMatch(Vec<String>);
+
+impl TextMatch {
+    pub(crate) fn matches(&self, text: &str) -> bool {
+        self.0.iter().any(|v| text.contains(v.as_str()))
+    }
+
+    /// Whether the whole text is one of the texts.
+    pub(crate) fn is(&self, text: &str) -> bool {
+        self.0.iter().any(|v| text == v.as_str())
+    }
+
+    /// The text with every occurrence of the texts taken out.
+    pub(crate) fn remove_from(&self, text: &str) -> String {
+        self.0
+            .iter()
+            .fold(text.to_owned(), |text, v| text.replace(v.as_str(), ""))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct FetchProfile {
+    pub(crate) ban_page: TextMatch,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct RootProfile {
+    pub(crate) tag_table: SelectorChain,
+    pub(crate) tag_link: SelectorChain,
+    pub(crate) category_title: SelectorChain,
+    pub(crate) tag_count: SelectorChain,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct TagProfile {
+    pub(crate) paginator: SelectorChain,
+    pub(crate) subject_item: SelectorChain,
+    pub(crate) subject_link: SelectorChain,
+    pub(crate) pub_line: SelectorChain,
+    pub(crate) rating: SelectorChain,
+    pub(crate) rating_people: SelectorChain,
+    pub(crate) description: SelectorChain,
+    pub(crate) next_link: SelectorChain,
+    pub(crate) texts: TagTexts,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct TagTexts {
+    pub(crate) insufficient_ratings: TextMatch,
+    pub(crate) no_ratings: TextMatch,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct SearchProfile {
+    pub(crate) data_marker: String,
+}
+
+/// The book fields a basic info label can fill.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum InfoField {
+    OriginTitle,
+    Subtitle,
+    Author,
+    Translator,
+    Press,
+    Producer,
+    PublicationYear,
+    PageNum,
+    Price,
+    Binding,
+    Series,
+    Isbn,
+    UnifiedBookNumber,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct BookTexts {
+    pub(crate) insufficient_ratings: TextMatch,
+    pub(crate) no_ratings: TextMatch,
+    pub(crate) wish: TextMatch,
+    pub(crate) reading: TextMatch,
+    pub(crate) read: TextMatch,
+    pub(crate) content_intro_heading: TextMatch,
+    pub(crate) author_intro_heading: TextMatch,
+    pub(crate) directory_heading: TextMatch,
+    pub(crate) series_heading: TextMatch,
+    pub(crate) directory_toggle: TextMatch,
+    pub(crate) review_unfold: TextMatch,
+    pub(crate) banned_title: TextMatch,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct BookProfile {
+    pub(crate) title: SelectorChain,
+    pub(crate) cover: SelectorChain,
+    pub(crate) cover_large: SelectorChain,
+    pub(crate) cover_small: SelectorChain,
+    pub(crate) info: SelectorChain,
+    pub(crate) info_label: SelectorChain,
+    pub(crate) rating_wrap: SelectorChain,
+    pub(crate) rating_num: SelectorChain,
+    pub(crate) rating_people: SelectorChain,
+    pub(crate) rating_self: SelectorChain,
+    pub(crate) collector: SelectorChain,
+    pub(crate) collector_link: SelectorChain,
+    pub(crate) related_info: SelectorChain,
+    pub(crate) content_intro: SelectorChain,
+    pub(crate) intro: SelectorChain,
+    pub(crate) intro_paragraph: SelectorChain,
+    pub(crate) directory_full: SelectorChain,
+    pub(crate) tags_section: SelectorChain,
+    pub(crate) tag: SelectorChain,
+    pub(crate) other_editions: SelectorChain,
+    pub(crate) series_block: SelectorChain,
+    pub(crate) series_heading: SelectorChain,
+    pub(crate) subject_link: SelectorChain,
+    pub(crate) recommendations: SelectorChain,
+    pub(crate) recommendation_link: SelectorChain,
+    pub(crate) review_item: SelectorChain,
+    pub(crate) review_title: SelectorChain,
+    pub(crate) review_author: SelectorChain,
+    pub(crate) review_rating: SelectorChain,
+    pub(crate) review_teaser: SelectorChain,
+    pub(crate) texts: BookTexts,
+    pub(crate) labels: HashMap<String, InfoField>,
+    pub(crate) star_labels: HashMap<String, u8>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct CommentsTexts {
+    pub(crate) heading: TextMatch,
+    pub(crate) total: TextMatch,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct CommentsProfile {
+    pub(crate) item: SelectorChain,
+    pub(crate) vote_count: SelectorChain,
+    pub(crate) info: SelectorChain,
+    pub(crate) info_part: SelectorChain,
+    pub(crate) user: SelectorChain,
+    pub(crate) time: SelectorChain,
+    pub(crate) text: SelectorChain,
+    pub(crate) section_heading: SelectorChain,
+    pub(crate) total_link: SelectorChain,
+    pub(crate) texts: CommentsTexts,
+}
+
+/// Where the parsers find things on the pages, see profiles/douban.toml.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Profile {
+    pub(crate) version: u32,
+    pub(crate) star_class_prefix: String,
+    pub(crate) fetch: FetchProfile,
+    pub(crate) root: RootProfile,
+    pub(crate) tag: TagProfile,
+    pub(crate) search: SearchProfile,
+    pub(crate) book: BookProfile,
+    pub(crate) comments: CommentsProfile,
+}
+
+impl Profile {
+    fn selector_chains(&self) -> Vec<&SelectorChain> {
+        let book = &self.book;
+        let comments = &self.comments;
+        vec![
+            &self.root.tag_table,
+            &self.root.tag_link,
+            &self.root.category_title,
+            &self.root.tag_count,
+            &self.tag.paginator,
+            &self.tag.subject_item,
+            &self.tag.subject_link,
+            &self.tag.pub_line,
+            &self.tag.rating,
+            &self.tag.rating_people,
+            &self.tag.description,
+            &self.tag.next_link,
+            &book.title,
+            &book.cover,
+            &book.cover_large,
+            &book.cover_small,
+            &book.info,
+            &book.info_label,
+            &book.rating_wrap,
+            &book.rating_num,
+            &book.rating_people,
+            &book.rating_self,
+            &book.collector,
+            &book.collector_link,
+            &book.related_info,
+            &book.content_intro,
+            &book.intro,
+            &book.intro_paragraph,
+            &book.directory_full,
+            &book.tags_section,
+            &book.tag,
+            &book.other_editions,
+            &book.series_block,
+            &book.series_heading,
+            &book.subject_link,
+            &book.recommendations,
+            &book.recommendation_link,
+            &book.review_item,
+            &book.review_title,
+            &book.review_author,
+            &book.review_rating,
+            &book.review_teaser,
+            &comments.item,
+            &comments.vote_count,
+            &comments.info,
+            &comments.info_part,
+            &comments.user,
+            &comments.time,
+            &comments.text,
+            &comments.section_heading,
+            &comments.total_link,
+        ]
+    }
+}
+
+fn parse(content: &str) -> anyhow::Result<Profile> {
+    let profile: Profile = toml::from_str(content)?;
+    if profile.version != PROFILE_VERSION {
+        return Err(anyhow!(
+            "unsupported profile version, version= {:?}, supported= {:?}",
+            profile.version,
+            PROFILE_VERSION
+        ));
+    }
+    // a typo should stop the run here, not abort it on the first page
+    for chain in profile.selector_chains() {
+        chain.check()?;
+    }
+
+    Ok(profile)
+}
+
+/// Replaces the built-in profile by the one in the file, use --profile=path.
+pub(crate) fn init(path: Option<String>) -> anyhow::Result<()> {
+    let path = match path {
+        Some(v) => v,
+        None => return Ok(()),
+    };
+    let content = fs::read_to_string(path.as_str())?;
+    let profile = parse(content.as_str())?;
+    info!(
+        "extraction profile loaded, path= {:?}, version= {:?}",
+        path, profile.version
+    );
+    *PROFILE.write().expect("failed to get PROFILE write lock") = Arc::new(profile);
+
+    Ok(())
+}
+
+pub(crate) fn get() -> Arc<Profile> {
+    PROFILE
+        .read()
+        .expect("failed to get PROFILE read lock")
+        .clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_profile_is_valid() {
+        let profile = parse(DEFAULT_PROFILE).expect("the built-in profile should be valid");
+        assert_eq!(profile.book.labels.get("ISBN"), Some(&InfoField::Isbn));
+        assert_eq!(profile.book.star_labels.len(), 5);
+    }
+
+    #[test]
+    fn classes_match_by_token() {
+        let profile = parse(DEFAULT_PROFILE).expect("the built-in profile should be valid");
+        // classes added and reordered since the profile was written
+        let html = scraper::Html::parse_document(
+            r#"<div class="clearbox rating_wrap new"><div class="clearfix rating_self"></div></div>"#,
+        );
+        let rating_wrap = profile
+            .book
+            .rating_wrap
+            .first(html.root_element())
+            .expect("selectors are valid")
+            .expect("rating wrap should match");
+        assert!(profile
+            .book
+            .rating_self
+            .first(rating_wrap)
+            .expect("selectors are valid")
+            .is_some());
+    }
+
+    #[test]
+    fn other_versions_are_rejected() {
+        let content = DEFAULT_PROFILE.replacen("version = 1", "version = 2", 1);
+        assert!(parse(content.as_str()).is_err());
+    }
+
+    #[test]
+    fn invalid_selectors_are_rejected() {
+        let content = DEFAULT_PROFILE.replacen("tag = [\"a.tag\"]", "tag = [\"a..tag\"]", 1);
+        assert!(parse(content.as_str()).is_err());
+    }
+}
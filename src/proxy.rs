@@ -1,3 +1,4 @@
+use crate::error::FetchError;
 use crate::utils::get_selector;
 use anyhow::anyhow;
 use anyhow::Context;
@@ -128,13 +129,13 @@ pub(crate) fn get_and_store_valid_proxies() -> anyhow::Result<()> {
     Ok(())
 }
 
-pub(crate) fn get_proxy_to_use() -> anyhow::Result<ProxyInfo> {
+pub(crate) fn get_proxy_to_use() -> Result<ProxyInfo, FetchError> {
     PROXIES
         .read()
         .expect("failed to get PROXIES read lock")
         .choose(&mut rand::thread_rng())
         .map(|v| (*v).clone())
-        .ok_or_else(|| anyhow!("failed to get random proxy"))
+        .ok_or(FetchError::NoProxy)
 }
 
 fn test_proxy_info(proxy_info: &ProxyInfo) -> anyhow::Result<bool> {
@@ -149,7 +150,7 @@ fn test_proxy_info(proxy_info: &ProxyInfo) -> anyhow::Result<bool> {
     let resp = client.get(TEST_URL).send()?;
 
     let status = resp.status();
-    let text = resp.text()?;
+    let text = crate::fetch::read_text(resp)?;
     trace!(
         "test prox info result, proxy_info: {:?}, response status: {:?}, response text: {:?}",
         proxy_info,
@@ -161,7 +162,7 @@ fn test_proxy_info(proxy_info: &ProxyInfo) -> anyhow::Result<bool> {
         return Ok(false);
     }
 
-    if text.contains("sec.douban.com") {
+    if crate::profile::get().fetch.ban_page.matches(&text) {
         return Ok(false);
     }
 
@@ -173,7 +174,7 @@ fn parse_kuaidaili_proxy_info(targets: &[i32]) -> Vec<ProxyInfo> {
 
     let mut proxy_infos: Vec<ProxyInfo> = Vec::new();
     for target in targets {
-        let url = format!("{}{}/", BASE_URL, target.to_string());
+        let url = format!("{}{}/", BASE_URL, target);
         debug!("a new page will be parsed, url= {:?}", url);
         match parse_kuaidaili_proxy_info_from_page(url.as_str()) {
             Ok(proxy_infos_) => proxy_infos.extend(proxy_infos_),
@@ -196,11 +197,13 @@ fn parse_kuaidaili_proxy_info_from_page(url: &str) -> anyhow::Result<Vec<ProxyIn
         ));
     }
 
-    let text = resp
-        .text()
+    let text = crate::fetch::read_text(resp)
         .with_context(|| format!("failed to parse proxy info, get text error, url= {:?}", url))?;
+    parse_kuaidaili_document(text.as_str(), url)
+}
 
-    let document = Html::parse_document(text.as_str());
+pub(crate) fn parse_kuaidaili_document(html: &str, url: &str) -> anyhow::Result<Vec<ProxyInfo>> {
+    let document = Html::parse_document(html);
     let tbody_selector = get_selector("tbody")?;
     match document.select(&tbody_selector).next() {
         Some(tbody) => {
@@ -234,23 +237,21 @@ fn parse_kuaidaili_proxy_info_from_tr(tr: ElementRef) -> anyhow::Result<ProxyInf
     let td_anonymous_selector = get_selector(r#"td[data-title="匿名度"]"#)?;
     let td_position_selector = get_selector(r#"td[data-title="位置"]"#)?;
 
-    let mut proxy_info = ProxyInfo::default();
-    proxy_info.ip = parse_kuaidaili_proxy_info_from_tr_inner(tr, td_ip_selector)?;
-    proxy_info.port = parse_kuaidaili_proxy_info_from_tr_inner(tr, td_port_selector)?;
-    proxy_info.scheme = parse_kuaidaili_proxy_info_from_tr_inner(tr, td_scheme_selector)?;
-    proxy_info.last_verified =
-        parse_kuaidaili_proxy_info_from_tr_inner(tr, td_last_verified_selector)?;
-    proxy_info.anonymous = parse_kuaidaili_proxy_info_from_tr_inner(tr, td_anonymous_selector)?;
-    proxy_info.position = parse_kuaidaili_proxy_info_from_tr_inner(tr, td_position_selector)?;
-
-    Ok(proxy_info)
+    Ok(ProxyInfo {
+        ip: parse_kuaidaili_proxy_info_from_tr_inner(tr, &td_ip_selector)?,
+        port: parse_kuaidaili_proxy_info_from_tr_inner(tr, &td_port_selector)?,
+        scheme: parse_kuaidaili_proxy_info_from_tr_inner(tr, &td_scheme_selector)?,
+        last_verified: parse_kuaidaili_proxy_info_from_tr_inner(tr, &td_last_verified_selector)?,
+        anonymous: parse_kuaidaili_proxy_info_from_tr_inner(tr, &td_anonymous_selector)?,
+        position: parse_kuaidaili_proxy_info_from_tr_inner(tr, &td_position_selector)?,
+    })
 }
 
 fn parse_kuaidaili_proxy_info_from_tr_inner(
     tr: ElementRef,
-    sel: Selector,
+    sel: &Selector,
 ) -> anyhow::Result<String> {
-    match tr.select(&sel).next() {
+    match tr.select(sel).next() {
         Some(td) => {
             let texts: Vec<&str> = td.text().filter(|t| !t.trim().is_empty()).collect();
             if texts.is_empty() {
@@ -271,7 +272,7 @@ fn parse_xicidaili_proxy_info(targets: &[i32]) -> Vec<ProxyInfo> {
 
     let mut proxy_infos: Vec<ProxyInfo> = Vec::new();
     for target in targets {
-        let url = format!("{}{}", BASE_URL, target.to_string());
+        let url = format!("{}{}", BASE_URL, target);
         debug!("a new page will be parsed, url= {:?}", url);
         match parse_xicidaili_proxy_info_from_page(url.as_str()) {
             Ok(proxy_infos_) => proxy_infos.extend(proxy_infos_),
@@ -294,21 +295,20 @@ fn parse_xicidaili_proxy_info_from_page(url: &str) -> anyhow::Result<Vec<ProxyIn
         ));
     }
 
-    let text = resp
-        .text()
+    let text = crate::fetch::read_text(resp)
         .with_context(|| format!("failed to parse proxy info, get text error, url= {:?}", url))?;
+    parse_xicidaili_document(text.as_str())
+}
 
+pub(crate) fn parse_xicidaili_document(html: &str) -> anyhow::Result<Vec<ProxyInfo>> {
     let mut proxy_infos: Vec<ProxyInfo> = Vec::new();
-    let document = Html::parse_document(text.as_str());
-    let tr_odd_selector = get_selector(r#"tr[class="odd"]"#)?;
-    let tr_even_selector = get_selector(r#"tr[class=""]"#)?;
+    let document = Html::parse_document(html);
+    // every row of the list but the header, odd and even rows alike
+    let tr_selector = get_selector("#ip_list tr")?;
 
-    let proxy_odd_infos =
-        parse_xicidaili_proxy_info_from_tr_iter(document.select(&tr_odd_selector))?;
-    proxy_infos.extend(proxy_odd_infos);
-    let proxy_even_infos =
-        parse_xicidaili_proxy_info_from_tr_iter(document.select(&tr_even_selector))?;
-    proxy_infos.extend(proxy_even_infos);
+    proxy_infos.extend(parse_xicidaili_proxy_info_from_tr_iter(
+        document.select(&tr_selector),
+    )?);
 
     Ok(proxy_infos)
 }
@@ -318,8 +318,10 @@ fn parse_xicidaili_proxy_info_from_tr_iter(tr_iter: Select) -> anyhow::Result<Ve
     let mut proxy_infos: Vec<ProxyInfo> = Vec::new();
     for tr in tr_iter {
         let mut td_iter = tr.select(&td_selector);
-        // skip country
-        td_iter.next();
+        // skip country, the header row has no td at all
+        if td_iter.next().is_none() {
+            continue;
+        }
         let mut proxy_info = ProxyInfo::default();
         // ip
         match td_iter.next() {
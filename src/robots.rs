@@ -0,0 +1,334 @@
+use crate::error::FetchError;
+use lazy_static::lazy_static;
+use log::{debug, info, warn};
+use reqwest::Url;
+use std::collections::{HashMap, HashSet};
+use std::sync::RwLock;
+use std::time;
+
+lazy_static! {
+    static ref ROBOTS: RwLock<HashMap<String, Robots>> = RwLock::new(HashMap::new());
+    static ref IGNORED_HOSTS: RwLock<HashSet<String>> = RwLock::new(HashSet::new());
+}
+
+// product token we match robots.txt groups against, besides "*". It is not sent, the
+// requests keep the browser user agent the site serves its regular pages to, the token
+// only makes sure a group written for this crawler applies to it.
+pub(crate) const ROBOTS_USER_AGENT: &str = "rosario";
+
+#[derive(Debug, Clone)]
+struct Rule {
+    allow: bool,
+    path: String,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Robots {
+    rules: Vec<Rule>,
+    crawl_delay: Option<time::Duration>,
+}
+
+impl Robots {
+    fn allow_all() -> Robots {
+        Robots::default()
+    }
+
+    /// The longest matching rule wins, `allow` wins a tie.
+    fn is_allowed(&self, path: &str) -> bool {
+        let mut matched: Option<&Rule> = None;
+        for rule in self.rules.iter() {
+            if !path_matches(rule.path.as_str(), path) {
+                continue;
+            }
+
+            matched = match matched {
+                Some(m)
+                    if m.path.len() > rule.path.len()
+                        || (m.path.len() == rule.path.len() && m.allow) =>
+                {
+                    Some(m)
+                }
+                _ => Some(rule),
+            };
+        }
+
+        matched.map(|rule| rule.allow).unwrap_or(true)
+    }
+}
+
+/// Matches a robots.txt path pattern, `*` matches any sequence and a trailing `$` anchors the end.
+fn path_matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(p) => (p, true),
+        None => (pattern, false),
+    };
+
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    if !path.starts_with(first) {
+        return false;
+    }
+    let mut rest = &path[first.len()..];
+    let parts: Vec<&str> = parts.collect();
+    for (idx, part) in parts.iter().enumerate() {
+        let is_last = idx + 1 == parts.len();
+        if is_last && anchored {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(pos) => rest = &rest[pos + part.len()..],
+            None => return false,
+        }
+    }
+
+    !anchored || rest.is_empty()
+}
+
+fn parse(content: &str, user_agent: &str) -> Robots {
+    let user_agent = user_agent.to_ascii_lowercase();
+    // (agents, robots) of every group
+    let mut groups: Vec<(Vec<String>, Robots)> = Vec::new();
+    let mut in_agent_lines = false;
+    for line in content.lines() {
+        let line = match line.find('#') {
+            Some(pos) => &line[..pos],
+            None => line,
+        };
+        let mut kv = line.splitn(2, ':');
+        let (key, value) = match (kv.next(), kv.next()) {
+            (Some(k), Some(v)) => (k.trim().to_ascii_lowercase(), v.trim()),
+            _ => continue,
+        };
+
+        if key == "user-agent" {
+            // consecutive user-agent lines share one group
+            if !in_agent_lines {
+                groups.push((Vec::new(), Robots::default()));
+            }
+            in_agent_lines = true;
+            if let Some((agents, _)) = groups.last_mut() {
+                agents.push(value.to_ascii_lowercase());
+            }
+            continue;
+        }
+        in_agent_lines = false;
+
+        let robots = match groups.last_mut() {
+            Some((_, robots)) => robots,
+            // rules before any user-agent line
+            None => continue,
+        };
+        match key.as_str() {
+            "allow" | "disallow" => {
+                // an empty disallow allows everything
+                if value.is_empty() {
+                    continue;
+                }
+                robots.rules.push(Rule {
+                    allow: key == "allow",
+                    path: value.to_owned(),
+                });
+            }
+            "crawl-delay" => match value.parse::<f64>() {
+                Ok(v) if 0.0 <= v => robots.crawl_delay = Some(time::Duration::from_secs_f64(v)),
+                _ => warn!("invalid crawl-delay in robots.txt, value= {:?}", value),
+            },
+            _ => (),
+        }
+    }
+
+    // "rosario" and "rosario/1.0" name us, "ro" does not
+    let specific = groups.iter().find(|(agents, _)| {
+        agents
+            .iter()
+            .any(|agent| agent.split('/').next().unwrap_or_default() == user_agent)
+    });
+    let wildcard = groups
+        .iter()
+        .find(|(agents, _)| agents.iter().any(|agent| agent == "*"));
+    specific
+        .or(wildcard)
+        .map(|(_, robots)| robots.clone())
+        .unwrap_or_default()
+}
+
+/// The rules the robots.txt response stands for, a missing robots.txt allows everything.
+///
+/// A 5xx, a ban or a failed request is handed back uncached, the page waiting on it is
+/// retried or requeued like any other fetch and robots.txt is fetched again with it.
+fn robots_from_fetch(res: Result<String, FetchError>) -> Result<Robots, FetchError> {
+    match res {
+        Ok(content) => Ok(parse(content.as_str(), ROBOTS_USER_AGENT)),
+        // no robots.txt, or it is not for us to read
+        Err(FetchError::NotFound { .. }) => Ok(Robots::allow_all()),
+        Err(FetchError::Status { status, .. }) if (400..500).contains(&status) => {
+            Ok(Robots::allow_all())
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Hosts we have permission to crawl regardless of their robots.txt.
+pub(crate) fn init(ignored_hosts: Vec<String>) {
+    if !ignored_hosts.is_empty() {
+        info!("ignore robots.txt for hosts: {:?}", ignored_hosts);
+    }
+    let mut hosts = IGNORED_HOSTS
+        .write()
+        .expect("failed to get IGNORED_HOSTS write lock");
+    hosts.extend(ignored_hosts.into_iter().map(|h| h.to_ascii_lowercase()));
+}
+
+fn is_ignored(host: &str) -> bool {
+    IGNORED_HOSTS
+        .read()
+        .expect("failed to get IGNORED_HOSTS read lock")
+        .contains(host)
+}
+
+fn get_robots(scheme: &str, host: &str) -> Result<Robots, FetchError> {
+    get_robots_with(scheme, host, |robots_url| {
+        crate::fetch::fetch_page(robots_url, robots_url)
+    })
+}
+
+fn get_robots_with<F>(scheme: &str, host: &str, fetch: F) -> Result<Robots, FetchError>
+where
+    F: FnOnce(&str) -> Result<String, FetchError>,
+{
+    if let Some(robots) = ROBOTS
+        .read()
+        .expect("failed to get ROBOTS read lock")
+        .get(host)
+    {
+        return Ok(robots.clone());
+    }
+
+    let robots_url = format!("{}://{}/robots.txt", scheme, host);
+    let robots = robots_from_fetch(fetch(robots_url.as_str()))?;
+    debug!("robots.txt loaded, host= {:?}, robots= {:?}", host, robots);
+
+    ROBOTS
+        .write()
+        .expect("failed to get ROBOTS write lock")
+        .insert(host.to_owned(), robots.clone());
+    Ok(robots)
+}
+
+/// Checks the url against its host's robots.txt, returns the crawl delay asked for.
+pub(crate) fn check(url: &str) -> Result<Option<time::Duration>, FetchError> {
+    let parsed = match Url::parse(url) {
+        Ok(v) => v,
+        Err(e) => {
+            warn!(
+                "failed to parse url, skip robots check, e= {:?}, url= {:?}",
+                e, url
+            );
+            return Ok(None);
+        }
+    };
+    let host = parsed.host_str().unwrap_or_default().to_ascii_lowercase();
+    if is_ignored(host.as_str()) {
+        return Ok(None);
+    }
+
+    let robots = get_robots(parsed.scheme(), host.as_str())?;
+    let path = match parsed.query() {
+        Some(query) => format!("{}?{}", parsed.path(), query),
+        None => parsed.path().to_owned(),
+    };
+    if !robots.is_allowed(path.as_str()) {
+        return Err(FetchError::Disallowed {
+            url: url.to_owned(),
+        });
+    }
+
+    Ok(robots.crawl_delay)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_patterns() {
+        assert!(path_matches("/subject/", "/subject/25862578/"));
+        assert!(!path_matches("/subject/", "/tag/"));
+        assert!(path_matches("/*/comments", "/subject/1/comments?start=20"));
+        assert!(path_matches("/*.jpg$", "/view/s1.jpg"));
+        assert!(!path_matches("/*.jpg$", "/view/s1.jpg?w=100"));
+        assert!(path_matches("/search$", "/search"));
+        assert!(!path_matches("/search$", "/search/"));
+    }
+
+    #[test]
+    fn longest_rule_wins() {
+        let robots = parse(
+            "User-agent: *\nDisallow: /subject_search\nAllow: /subject_search/help\nDisallow: /j/\nAllow: /j/\n",
+            ROBOTS_USER_AGENT,
+        );
+        assert!(!robots.is_allowed("/subject_search?search_text=1"));
+        assert!(robots.is_allowed("/subject_search/help"));
+        // allow wins a tie
+        assert!(robots.is_allowed("/j/search"));
+        assert!(robots.is_allowed("/subject/1/"));
+    }
+
+    #[test]
+    fn groups_match_the_product_token() {
+        let content = "User-agent: ro\nDisallow: /\n\nUser-agent: Rosario/1.0\nDisallow: /tag/\n\nUser-agent: *\nDisallow: /subject/\n";
+        let robots = parse(content, ROBOTS_USER_AGENT);
+        assert!(!robots.is_allowed("/tag/小说"));
+        assert!(robots.is_allowed("/subject/1/"));
+
+        let robots = parse("User-agent: ro\nDisallow: /\n", ROBOTS_USER_AGENT);
+        assert!(robots.is_allowed("/subject/1/"));
+    }
+
+    #[test]
+    fn status_decides_without_robots_txt() {
+        let status = |status: u16| {
+            robots_from_fetch(Err(FetchError::Status {
+                url: "https://book.douban.com/robots.txt".to_owned(),
+                status,
+            }))
+        };
+        let not_found = robots_from_fetch(Err(FetchError::NotFound {
+            url: "https://book.douban.com/robots.txt".to_owned(),
+        }));
+        assert!(not_found.expect("404 allows").is_allowed("/subject/1/"));
+        assert!(status(401).expect("4xx allows").is_allowed("/subject/1/"));
+        assert!(status(503).is_err());
+        let banned = robots_from_fetch(Err(FetchError::Banned {
+            url: "https://book.douban.com/robots.txt".to_owned(),
+            status: 429,
+        }));
+        assert!(banned.is_err());
+        assert!(robots_from_fetch(Err(FetchError::NoProxy)).is_err());
+    }
+
+    #[test]
+    fn failed_fetches_are_not_cached() {
+        // a host of its own, the cache is shared by the tests
+        let host = "robots-503.example.com";
+        let unavailable = get_robots_with("https", host, |url| {
+            Err(FetchError::Status {
+                url: url.to_owned(),
+                status: 503,
+            })
+        });
+        assert!(unavailable.is_err());
+
+        let robots = get_robots_with("https", host, |_| {
+            Ok("User-agent: *\nDisallow: /private/\n".to_owned())
+        })
+        .expect("robots.txt answers now");
+        assert!(robots.is_allowed("/subject/1/"));
+        assert!(!robots.is_allowed("/private/1"));
+
+        // cached once it answered
+        let cached =
+            get_robots_with("https", host, |_| panic!("fetched again")).expect("cached robots.txt");
+        assert!(cached.is_allowed("/subject/1/"));
+    }
+}
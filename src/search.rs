@@ -0,0 +1,100 @@
+use crate::book::ListingItem;
+use log::warn;
+use std::collections::HashSet;
+use std::fs;
+
+// a keyword matching thousands of books is better narrowed down than paged through
+const MAX_SEARCH_PAGES: u32 = 20;
+
+/// What the lines of a query file are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum QueryKind {
+    Keyword,
+    /// an isbn finds one edition, on the first result page
+    Isbn,
+    /// the results also list books about the author, only the ones by the author are kept
+    Author,
+}
+
+/// One douban search.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Query {
+    pub(crate) kind: QueryKind,
+    pub(crate) text: String,
+}
+
+impl Query {
+    pub(crate) fn max_pages(&self) -> u32 {
+        match self.kind {
+            QueryKind::Isbn => 1,
+            QueryKind::Keyword | QueryKind::Author => MAX_SEARCH_PAGES,
+        }
+    }
+
+    /// Whether the result is worth crawling for this query.
+    pub(crate) fn keeps(&self, item: &ListingItem) -> bool {
+        match self.kind {
+            QueryKind::Keyword | QueryKind::Isbn => true,
+            // the author comes first in the pub line
+            QueryKind::Author => item.pub_line.contains(self.text.as_str()),
+        }
+    }
+}
+
+/// The queries of the file, one per line, use --keywords, --isbns and --authors.
+pub(crate) fn load(path: &str, kind: QueryKind) -> anyhow::Result<Vec<Query>> {
+    let content = fs::read_to_string(path)?;
+    Ok(parse_queries(content.as_str(), kind))
+}
+
+/// Empty lines and lines starting with '#' are skipped, so are repeated queries,
+/// isbns are searched as ISBN-13 so both forms of one isbn are one query.
+fn parse_queries(content: &str, kind: QueryKind) -> Vec<Query> {
+    let mut texts: HashSet<String> = HashSet::new();
+    let mut queries = Vec::new();
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let text = match kind {
+            QueryKind::Isbn => match crate::isbn::parse(line) {
+                Some((isbn13, _)) => isbn13,
+                None => {
+                    warn!("invalid isbn, skip search query, line= {:?}", line);
+                    continue;
+                }
+            },
+            QueryKind::Keyword | QueryKind::Author => line.to_owned(),
+        };
+        if texts.insert(text.clone()) {
+            queries.push(Query { kind, text });
+        }
+    }
+
+    queries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_query_lines() {
+        let content =
+            "# from the reading list\n978-7-5442-7087-8\n\n7544270874\n9787544270878\n123\n";
+        let queries = parse_queries(content, QueryKind::Isbn);
+        let texts: Vec<&str> = queries.iter().map(|q| q.text.as_str()).collect();
+        assert_eq!(texts, vec!["9787544270878"]);
+
+        let author = parse_queries(" 东野圭吾 \n东野圭吾\n", QueryKind::Author);
+        assert_eq!(author.len(), 1);
+        let item = |pub_line: &str| ListingItem {
+            pub_line: pub_line.to_owned(),
+            ..Default::default()
+        };
+        assert!(author[0].keeps(&item("[日] 东野圭吾 / 李盈春 / 南海出版公司 / 2014-5")));
+        assert!(!author[0].keeps(&item("王小明 / 东方出版社 / 2019-1")));
+    }
+}
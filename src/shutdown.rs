@@ -0,0 +1,26 @@
+use log::warn;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static SHUTDOWN_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Installs the SIGINT/SIGTERM handler.
+///
+/// The first signal asks the crawler to stop after the in-flight item, a
+/// second one exits right away, stored books are never half written either way.
+pub(crate) fn init() -> anyhow::Result<()> {
+    ctrlc::set_handler(|| {
+        if SHUTDOWN_REQUESTED.swap(true, Ordering::SeqCst) {
+            warn!("second signal received, exit now");
+            crate::checkpoint::flush();
+            log::logger().flush();
+            std::process::exit(crate::EXIT_INTERRUPTED);
+        }
+        warn!("signal received, stop after the current item, send again to exit now");
+    })?;
+
+    Ok(())
+}
+
+pub(crate) fn requested() -> bool {
+    SHUTDOWN_REQUESTED.load(Ordering::SeqCst)
+}
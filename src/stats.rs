@@ -0,0 +1,76 @@
+use crate::book::QualityReport;
+use crate::error::{Error, ErrorAction};
+use lazy_static::lazy_static;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::RwLock;
+
+lazy_static! {
+    static ref STATS: RwLock<Stats> = RwLock::new(Stats::default());
+}
+
+#[derive(Default, Clone)]
+pub(crate) struct Stats {
+    pub(crate) book_stored: usize,
+    pub(crate) book_already_stored: usize,
+    pub(crate) retried: usize,
+    pub(crate) requeued: usize,
+    pub(crate) skipped: usize,
+    pub(crate) errors: BTreeMap<&'static str, usize>,
+    /// validation issues by rule, blocking or not
+    pub(crate) quality_issues: BTreeMap<String, usize>,
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "book_stored: {}, book_already_stored: {}, retried: {}, requeued: {}, skipped: {}, errors: {:?}, quality_issues: {:?}",
+            self.book_stored,
+            self.book_already_stored,
+            self.retried,
+            self.requeued,
+            self.skipped,
+            self.errors,
+            self.quality_issues
+        )
+    }
+}
+
+fn update<F: FnOnce(&mut Stats)>(f: F) {
+    let mut stats = STATS.write().expect("failed to get STATS write lock");
+    f(&mut stats);
+}
+
+pub(crate) fn add_book_stored() {
+    update(|stats| stats.book_stored += 1);
+}
+
+pub(crate) fn add_book_already_stored() {
+    update(|stats| stats.book_already_stored += 1);
+}
+
+/// Counts the error by its kind, and the decision taken for it.
+pub(crate) fn add_error(e: &Error, action: ErrorAction) {
+    update(|stats| {
+        *stats.errors.entry(e.kind()).or_insert(0) += 1;
+        match action {
+            ErrorAction::Retry => stats.retried += 1,
+            ErrorAction::Requeue => stats.requeued += 1,
+            ErrorAction::Skip => stats.skipped += 1,
+            ErrorAction::Abort => (),
+        }
+    });
+}
+
+pub(crate) fn add_quality_issues(report: &QualityReport) {
+    update(|stats| {
+        for issue in report.issues.iter() {
+            *stats.quality_issues.entry(issue.rule.clone()).or_insert(0) += 1;
+        }
+    });
+}
+
+pub(crate) fn snapshot() -> Stats {
+    STATS.read().expect("failed to get STATS read lock").clone()
+}
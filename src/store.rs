@@ -1,13 +1,19 @@
-use anyhow::Context;
+use crate::book::ListingItem;
+use crate::error::StoreError;
 use lazy_static::lazy_static;
 use log::{debug, warn};
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fs;
+use std::io::Write;
 use std::path;
 use std::sync::RwLock;
 
 lazy_static! {
     static ref STORED_BOOK_IDS: RwLock<HashSet<String>> = RwLock::new(HashSet::new());
+    // normalized isbn-13 -> ids of the books with it, douban has several subjects for some isbns
+    static ref ISBN_INDEX: RwLock<HashMap<String, BTreeSet<String>>> = RwLock::new(HashMap::new());
+    // book id -> tags the book was found under
+    static ref BOOK_TAGS: RwLock<HashMap<String, BTreeSet<String>>> = RwLock::new(HashMap::new());
 }
 
 fn add_stored_book_id(book_id: &str) {
@@ -34,9 +40,175 @@ pub(crate) fn current_store_count() -> usize {
         .len()
 }
 
+/// Looks the stored books up by isbn, either form, hyphens allowed. Returns their book ids.
+pub(crate) fn find_by_isbn(isbn: &str) -> Vec<String> {
+    let isbn13 = match crate::isbn::parse(isbn) {
+        Some((isbn13, _)) => isbn13,
+        None => return Vec::new(),
+    };
+    ISBN_INDEX
+        .read()
+        .expect("failed to get ISBN_INDEX read lock")
+        .get(&isbn13)
+        .map(|book_ids| book_ids.iter().cloned().collect())
+        .unwrap_or_default()
+}
+
+fn add_isbn_index(isbn13: &str, book_id: &str) {
+    ISBN_INDEX
+        .write()
+        .expect("failed to get ISBN_INDEX write lock")
+        .entry(isbn13.to_owned())
+        .or_default()
+        .insert(book_id.to_owned());
+}
+
+/// The tags whose listing the book was found in, stored or not.
+pub(crate) fn book_tags(book_url: &str) -> BTreeSet<String> {
+    let book_id = crate::utils::parse_book_id(book_url);
+    BOOK_TAGS
+        .read()
+        .expect("failed to get BOOK_TAGS read lock")
+        .get(&book_id)
+        .cloned()
+        .unwrap_or_default()
+}
+
+fn insert_book_tag(book_id: &str, tag: &str) -> bool {
+    BOOK_TAGS
+        .write()
+        .expect("failed to get BOOK_TAGS write lock")
+        .entry(book_id.to_owned())
+        .or_default()
+        .insert(tag.to_owned())
+}
+
+/// Records that the book shows up in the listing of the tag.
+pub(crate) fn add_book_tag(book_url: &str, tag: &str) -> Result<(), StoreError> {
+    let book_id = crate::utils::parse_book_id(book_url);
+    if !insert_book_tag(book_id.as_str(), tag) {
+        return Ok(());
+    }
+
+    fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(BOOK_TAGS_FILE)
+        .and_then(|mut file| file.write_all(format!("{}\t{}\n", book_id, tag).as_bytes()))
+        .map_err(|e| StoreError::Io {
+            path: BOOK_TAGS_FILE.to_owned(),
+            source: e,
+        })
+}
+
+/// Keeps the books of a tag or search page as listed, a catalog to pick the books worth a full fetch from.
+///
+/// "listing_url rank book_id url title pub_line rating_state score score_num description" per line,
+/// tab separated. A page listed again in a later run is appended again, the last lines win.
+pub(crate) fn add_listing_items(
+    listing_url: &str,
+    items: &[ListingItem],
+) -> Result<(), StoreError> {
+    let clean = |s: &str| s.replace(&['\t', '\n', '\r'][..], " ");
+    let content = items.iter().fold(String::new(), |mut content, item| {
+        content.push_str(
+            format!(
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+                clean(listing_url),
+                item.rank,
+                item.id,
+                clean(item.url.as_str()),
+                clean(item.title.as_str()),
+                clean(item.pub_line.as_str()),
+                item.rating_state,
+                item.score.map(|v| v.to_string()).unwrap_or_default(),
+                item.score_num.map(|v| v.to_string()).unwrap_or_default(),
+                clean(item.description.as_str()),
+            )
+            .as_str(),
+        );
+        content
+    });
+
+    fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(LISTING_ITEMS_FILE)
+        .and_then(|mut file| file.write_all(content.as_bytes()))
+        .map_err(|e| StoreError::Io {
+            path: LISTING_ITEMS_FILE.to_owned(),
+            source: e,
+        })
+}
+
 const STORE_TARGET_DIR: &str = "books/";
+const TMP_FILE_PREFIX: &str = ".tmp_";
+// "isbn13 book_id" per line
+const ISBN_INDEX_FILE: &str = "isbn_index";
+// "book_id\ttag" per line, tags may have spaces
+const BOOK_TAGS_FILE: &str = "book_tags";
+// one listed book per line, see `add_listing_items`
+const LISTING_ITEMS_FILE: &str = "listing_items";
+
+fn load_book_tags() -> anyhow::Result<()> {
+    if !path::Path::new(BOOK_TAGS_FILE).is_file() {
+        return Ok(());
+    }
+
+    let content = fs::read_to_string(BOOK_TAGS_FILE)?;
+    for line in content.lines() {
+        let blocks: Vec<_> = line.splitn(2, '\t').collect();
+        if blocks.len() != 2 {
+            warn!(
+                "load book tags error, blocks count is not 2, line= {:?}",
+                line
+            );
+            continue;
+        }
+        insert_book_tag(blocks[0], blocks[1]);
+    }
+
+    Ok(())
+}
+
+fn load_isbn_index() -> anyhow::Result<()> {
+    if !path::Path::new(ISBN_INDEX_FILE).is_file() {
+        return Ok(());
+    }
+
+    let content = fs::read_to_string(ISBN_INDEX_FILE)?;
+    for line in content.lines() {
+        let blocks: Vec<_> = line.split(' ').collect();
+        if blocks.len() != 2 {
+            warn!(
+                "load isbn index error, blocks count is not 2, line= {:?}",
+                line
+            );
+            continue;
+        }
+        add_isbn_index(blocks[0], blocks[1]);
+    }
+
+    Ok(())
+}
+
+fn append_isbn_index(isbn13: &str, book_id: &str) -> Result<(), StoreError> {
+    add_isbn_index(isbn13, book_id);
+    fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(ISBN_INDEX_FILE)
+        .and_then(|mut file| file.write_all(format!("{} {}\n", isbn13, book_id).as_bytes()))
+        .map_err(|e| StoreError::Io {
+            path: ISBN_INDEX_FILE.to_owned(),
+            source: e,
+        })
+}
 
 pub(crate) fn init() -> anyhow::Result<()> {
+    load_isbn_index()?;
+    load_book_tags()?;
+
     fs::create_dir_all(STORE_TARGET_DIR)?;
     for entry in fs::read_dir(STORE_TARGET_DIR)? {
         let entry = match entry {
@@ -55,6 +227,15 @@ pub(crate) fn init() -> anyhow::Result<()> {
             }
         };
 
+        // left over by a write that did not finish
+        if name.starts_with(TMP_FILE_PREFIX) {
+            debug!("remove unfinished book file, name= {:?}", name);
+            if let Err(e) = fs::remove_file(entry.path()) {
+                warn!("failed to remove unfinished book file, e= {:?}", e);
+            }
+            continue;
+        }
+
         let name_blocks: Vec<_> = name
             .rsplit('_')
             .filter_map(|v| {
@@ -82,19 +263,79 @@ pub(crate) fn init() -> anyhow::Result<()> {
     Ok(())
 }
 
-pub(crate) fn store(book_url: &str, book: crate::book::Book) -> anyhow::Result<()> {
+// in chars, well below the 255 bytes most file systems allow for a name
+const MAX_FILE_NAME_TITLE_LEN: usize = 60;
+
+/// The title as part of a file name, "上/下" would otherwise point into a directory.
+fn file_name_title(title: &str) -> String {
+    title
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | '\0' => '-',
+            c if c.is_control() => ' ',
+            c => c,
+        })
+        .take(MAX_FILE_NAME_TITLE_LEN)
+        .collect::<String>()
+        .trim_start_matches('.')
+        .to_owned()
+}
+
+pub(crate) fn store(book_url: &str, book: crate::book::Book) -> Result<(), StoreError> {
     let book_id = crate::utils::parse_book_id(book_url);
-    let file_name = format!("{}_{}", book.title, book_id);
-    let file_name_for_err = file_name.clone();
+    let isbn13 = book.isbn13.clone();
+    let file_name = format!("{}_{}", file_name_title(book.title.as_str()), book_id);
     let path = path::Path::new(STORE_TARGET_DIR).join(file_name);
-    fs::write(path, format!("{}", book)).with_context(|| {
-        format!(
-            "store book to file error, book_url= {:?}, file_name= {:?}",
-            book_url, file_name_for_err
-        )
-    })?;
+    let tmp_path =
+        path::Path::new(STORE_TARGET_DIR).join(format!("{}{}", TMP_FILE_PREFIX, book_id));
+    // write then rename, so a killed process never leaves a half written book behind
+    fs::write(&tmp_path, format!("{}", book))
+        .and_then(|_| fs::rename(&tmp_path, &path))
+        .map_err(|e| StoreError::Io {
+            path: path.to_string_lossy().into_owned(),
+            source: e,
+        })?;
 
     add_stored_book_id(book_id.as_str());
+    // the book is stored by now, failing it would only fetch and store it again
+    if let Some(isbn13) = isbn13 {
+        if let Err(e) = append_isbn_index(isbn13.as_str(), book_id.as_str()) {
+            warn!(
+                "failed to append isbn index, e= {:?}, book_id= {:?}",
+                e, book_id
+            );
+        }
+    }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn titles_make_plain_file_names() {
+        assert_eq!(
+            file_name_title("明朝那些事儿(上/下)"),
+            "明朝那些事儿(上-下)"
+        );
+        assert_eq!(file_name_title("..\\A\nB"), "-A B");
+        assert_eq!(
+            file_name_title("长".repeat(100).as_str()).chars().count(),
+            MAX_FILE_NAME_TITLE_LEN
+        );
+    }
+
+    #[test]
+    fn one_isbn_finds_every_book() {
+        add_isbn_index("9780804429573", "1003000");
+        add_isbn_index("9780804429573", "1003001");
+        add_isbn_index("9780804429573", "1003000");
+        assert_eq!(
+            find_by_isbn("978-0-8044-2957-3"),
+            vec!["1003000".to_owned(), "1003001".to_owned()]
+        );
+        assert!(find_by_isbn("not an isbn").is_empty());
+    }
+}
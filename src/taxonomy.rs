@@ -0,0 +1,327 @@
+use crate::book::{TagCategory, TagEntry};
+use lazy_static::lazy_static;
+use log::{debug, info, warn};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::io;
+use std::path;
+use std::sync::RwLock;
+
+lazy_static! {
+    static ref FILTER: RwLock<Filter> = RwLock::new(Filter::default());
+    static ref REACHES: RwLock<BTreeMap<String, (Reach, ListingEnd)>> =
+        RwLock::new(BTreeMap::new());
+}
+
+// the categories and tags of the last root page, one tag per line
+const TAXONOMY_FILE: &str = "taxonomy";
+const TAXONOMY_FILE_TMP: &str = "taxonomy.tmp";
+// how far each tag listing went, one listing per line
+const REACH_FILE: &str = "tag_reach";
+const REACH_FILE_TMP: &str = "tag_reach.tmp";
+const FIELD_SEPARATOR: char = '\t';
+
+/// Why the crawler stopped following a tag listing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ListingEnd {
+    /// the page has no "后页" link
+    LastPage,
+    /// the page lists no book, douban serves these past the depth it lists
+    EmptyPage,
+    /// the page lists only books of the pages before, or the "后页" link leads back to one
+    RepeatedPage,
+    /// a page could not be fetched and the paginator shows no more pages
+    FetchFailed,
+    /// the "后页" links went on for too long
+    PageLimit,
+}
+
+impl ListingEnd {
+    fn as_str(self) -> &'static str {
+        match self {
+            ListingEnd::LastPage => "last_page",
+            ListingEnd::EmptyPage => "empty_page",
+            ListingEnd::RepeatedPage => "repeated_page",
+            ListingEnd::FetchFailed => "fetch_failed",
+            ListingEnd::PageLimit => "page_limit",
+        }
+    }
+
+    fn from_str(s: &str) -> Option<ListingEnd> {
+        match s {
+            "last_page" => Some(ListingEnd::LastPage),
+            "empty_page" => Some(ListingEnd::EmptyPage),
+            "repeated_page" => Some(ListingEnd::RepeatedPage),
+            "fetch_failed" => Some(ListingEnd::FetchFailed),
+            "page_limit" => Some(ListingEnd::PageLimit),
+            _ => None,
+        }
+    }
+}
+
+/// How many books a tag listing really reaches, in one sort order.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Reach {
+    /// the last page number the paginator shows
+    pub(crate) page_count: Option<i32>,
+    /// pages fetched, the empty or repeated one included
+    pub(crate) pages: usize,
+    /// distinct books listed
+    pub(crate) books: usize,
+}
+
+/// The tags to crawl, everything by default.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Filter {
+    /// only these categories, all when empty
+    pub(crate) include_categories: Vec<String>,
+    pub(crate) exclude_categories: Vec<String>,
+    /// only these tags, all when empty
+    pub(crate) include_tags: Vec<String>,
+    pub(crate) exclude_tags: Vec<String>,
+    /// tags with fewer books are left out, tags without a count are kept
+    pub(crate) min_count: Option<u32>,
+}
+
+impl Filter {
+    fn includes(&self, category: &TagCategory, tag: &TagEntry) -> bool {
+        let listed = |names: &[String], name: &str| names.iter().any(|n| n == name);
+        if !self.include_categories.is_empty()
+            && !listed(&self.include_categories, category.name.as_str())
+        {
+            return false;
+        }
+        if listed(&self.exclude_categories, category.name.as_str()) {
+            return false;
+        }
+        if !self.include_tags.is_empty() && !listed(&self.include_tags, tag.name.as_str()) {
+            return false;
+        }
+        if listed(&self.exclude_tags, tag.name.as_str()) {
+            return false;
+        }
+        match (self.min_count, tag.count) {
+            (Some(min_count), Some(count)) => min_count <= count,
+            _ => true,
+        }
+    }
+}
+
+/// Narrows the tags to crawl, use --categories, --exclude-categories, --tags,
+/// --exclude-tags and --min-tag-count.
+pub(crate) fn init(filter: Filter) -> anyhow::Result<()> {
+    info!("tag filter: {:?}", filter);
+    *FILTER.write().expect("failed to get FILTER write lock") = filter;
+
+    if !path::Path::new(REACH_FILE).is_file() {
+        debug!("no tag reach file found");
+        return Ok(());
+    }
+    let content = fs::read_to_string(REACH_FILE)?;
+    let mut reaches = REACHES.write().expect("failed to get REACHES write lock");
+    for line in content.lines() {
+        match parse_reach_line(line) {
+            Some((listing_url, reach)) => {
+                reaches.insert(listing_url, reach);
+            }
+            None => warn!("load tag reach error, bad line, line= {:?}", line),
+        }
+    }
+    debug!("load tag reaches success, count= {:?}", reaches.len());
+
+    Ok(())
+}
+
+fn parse_reach_line(line: &str) -> Option<(String, (Reach, ListingEnd))> {
+    let blocks: Vec<_> = line.split(FIELD_SEPARATOR).collect();
+    if blocks.len() != 5 {
+        return None;
+    }
+
+    let reach = Reach {
+        page_count: blocks[1].parse().ok(),
+        pages: blocks[2].parse().ok()?,
+        books: blocks[3].parse().ok()?,
+    };
+    Some((
+        blocks[0].to_owned(),
+        (reach, ListingEnd::from_str(blocks[4])?),
+    ))
+}
+
+/// Records how far the listing starting at `listing_url` went, replacing the record of a previous run.
+pub(crate) fn record_reach(listing_url: &str, reach: Reach, end: ListingEnd) {
+    let mut reaches = REACHES.write().expect("failed to get REACHES write lock");
+    reaches.insert(clean_field(listing_url), (reach, end));
+
+    let content = reaches
+        .iter()
+        .fold(String::new(), |mut content, (url, (reach, end))| {
+            content.push_str(
+                format!(
+                    "{}{sep}{}{sep}{}{sep}{}{sep}{}\n",
+                    url,
+                    reach.page_count.map(|c| c.to_string()).unwrap_or_default(),
+                    reach.pages,
+                    reach.books,
+                    end.as_str(),
+                    sep = FIELD_SEPARATOR
+                )
+                .as_str(),
+            );
+            content
+        });
+    if let Err(e) =
+        fs::write(REACH_FILE_TMP, content).and_then(|_| fs::rename(REACH_FILE_TMP, REACH_FILE))
+    {
+        warn!(
+            "failed to store tag reach, e= {:?}, listing_url= {:?}",
+            e, listing_url
+        );
+    }
+}
+
+/// The tags the filter lets through, each once even when listed under several categories.
+pub(crate) fn selected(categories: &[TagCategory]) -> Vec<TagEntry> {
+    let filter = FILTER.read().expect("failed to get FILTER read lock");
+    let mut hrefs: HashSet<&str> = HashSet::new();
+    let mut tags = Vec::new();
+    for category in categories {
+        for tag in category.tags.iter() {
+            if !filter.includes(category, tag) {
+                debug!(
+                    "tag filtered out, category= {:?}, tag= {:?}",
+                    category.name, tag
+                );
+                continue;
+            }
+            if hrefs.insert(tag.href.as_str()) {
+                tags.push(tag.clone());
+            }
+        }
+    }
+
+    tags
+}
+
+fn clean_field(s: &str) -> String {
+    s.replace(&[FIELD_SEPARATOR, '\n', '\r'][..], " ")
+}
+
+/// Keeps the taxonomy for the next run, in case the root page can not be fetched then.
+pub(crate) fn store(categories: &[TagCategory]) -> io::Result<()> {
+    let mut content = String::new();
+    for category in categories {
+        for tag in category.tags.iter() {
+            content.push_str(
+                format!(
+                    "{}{sep}{}{sep}{}{sep}{}\n",
+                    clean_field(category.name.as_str()),
+                    clean_field(tag.name.as_str()),
+                    clean_field(tag.href.as_str()),
+                    tag.count.map(|c| c.to_string()).unwrap_or_default(),
+                    sep = FIELD_SEPARATOR
+                )
+                .as_str(),
+            );
+        }
+    }
+    fs::write(TAXONOMY_FILE_TMP, content)?;
+    fs::rename(TAXONOMY_FILE_TMP, TAXONOMY_FILE)
+}
+
+/// The taxonomy stored by a previous run, empty when there is none.
+pub(crate) fn load() -> io::Result<Vec<TagCategory>> {
+    if !path::Path::new(TAXONOMY_FILE).is_file() {
+        debug!("no taxonomy file found");
+        return Ok(Vec::new());
+    }
+
+    let content = fs::read_to_string(TAXONOMY_FILE)?;
+    let mut categories: Vec<TagCategory> = Vec::new();
+    for line in content.lines() {
+        let blocks: Vec<_> = line.split(FIELD_SEPARATOR).collect();
+        if blocks.len() != 4 {
+            warn!("load taxonomy error, bad line, line= {:?}", line);
+            continue;
+        }
+        let tag = TagEntry {
+            name: blocks[1].to_owned(),
+            href: blocks[2].to_owned(),
+            count: blocks[3].parse().ok(),
+        };
+        match categories.iter_mut().find(|c| c.name == blocks[0]) {
+            Some(category) => category.tags.push(tag),
+            None => categories.push(TagCategory {
+                name: blocks[0].to_owned(),
+                tags: vec![tag],
+            }),
+        }
+    }
+
+    Ok(categories)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn category(name: &str, tags: &[(&str, Option<u32>)]) -> TagCategory {
+        TagCategory {
+            name: name.to_owned(),
+            tags: tags
+                .iter()
+                .map(|(tag, count)| TagEntry {
+                    name: tag.to_string(),
+                    href: format!("/tag/{}", tag),
+                    count: *count,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn filter_by_category_and_count() {
+        let literature = category("文学", &[("小说", Some(6345612)), ("诗词", Some(900))]);
+        let science = category("科技", &[("编程", Some(148736)), ("算法", None)]);
+        let filter = Filter {
+            exclude_categories: vec!["科技".to_owned()],
+            min_count: Some(1000),
+            ..Default::default()
+        };
+        let included: Vec<&str> = [&literature, &science]
+            .iter()
+            .flat_map(|c| c.tags.iter().map(move |t| (c, t)))
+            .filter(|(c, t)| filter.includes(c, t))
+            .map(|(_, t)| t.name.as_str())
+            .collect();
+        assert_eq!(included, vec!["小说"]);
+
+        // a tag without a count is kept
+        let filter = Filter {
+            include_categories: vec!["科技".to_owned()],
+            min_count: Some(1000),
+            ..Default::default()
+        };
+        assert!(filter.includes(&science, &science.tags[1]));
+        assert!(!filter.includes(&literature, &literature.tags[0]));
+    }
+
+    #[test]
+    fn parse_reach_lines() {
+        let (url, (reach, end)) = parse_reach_line(
+            "https://book.douban.com/tag/小说?start=0&type=T\t50\t50\t1000\tempty_page",
+        )
+        .expect("a full line should parse");
+        assert_eq!(url, "https://book.douban.com/tag/小说?start=0&type=T");
+        assert_eq!(reach.page_count, Some(50));
+        assert_eq!((reach.pages, reach.books), (50, 1000));
+        assert_eq!(end, ListingEnd::EmptyPage);
+
+        // a listing without a paginator has no page count
+        let (_, (reach, _)) = parse_reach_line("/tag/诗词?start=0&type=T\t\t1\t12\tlast_page")
+            .expect("an empty page count should parse");
+        assert_eq!(reach.page_count, None);
+        assert!(parse_reach_line("/tag/诗词?start=0&type=T\t\t1\t12\tdone").is_none());
+    }
+}
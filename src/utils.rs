@@ -1,9 +1,19 @@
-use anyhow::{anyhow, Context};
+use crate::error::ParseError;
 use ego_tree::NodeRef;
+use lazy_static::lazy_static;
+use percent_encoding::percent_decode_str;
+use reqwest::Url;
 use scraper::element_ref::ElementRef;
 use scraper::node::Node;
 use scraper::Selector;
+use std::collections::HashMap;
 use std::iter::Iterator;
+use std::sync::{Arc, RwLock};
+
+lazy_static! {
+    // the selectors come from the code and the profile, a fixed set
+    static ref SELECTORS: RwLock<HashMap<String, Arc<Selector>>> = RwLock::new(HashMap::new());
+}
 
 pub(crate) fn parse_book_id(book_page_url: &str) -> String {
     let url_segments = book_page_url
@@ -18,10 +28,63 @@ pub(crate) fn parse_book_id(book_page_url: &str) -> String {
     }
 }
 
-pub(crate) fn get_selector(selector_str: &str) -> anyhow::Result<Selector> {
-    Selector::parse(selector_str)
-        .map_err(|e| anyhow!("{:?}", e))
-        .with_context(|| format!("get selector error, selector_str= {:?}", selector_str))
+/// The tag name of a tag page url, "https://book.douban.com/tag/小说?start=20&type=T" gives "小说".
+pub(crate) fn parse_tag_name(tag_page_url: &str) -> Option<String> {
+    let url = Url::parse(tag_page_url).ok()?;
+    let mut segments = url.path_segments()?;
+    segments.find(|segment| *segment == "tag")?;
+    let name = percent_decode_str(segments.next()?).decode_utf8().ok()?;
+    if name.is_empty() {
+        None
+    } else {
+        Some(name.into_owned())
+    }
+}
+
+/// The numeric id after `/{kind}/` in a douban link,
+/// e.g. "https://book.douban.com/press/2153" with kind "press" gives "2153".
+pub(crate) fn parse_href_id(href: &str, kind: &str) -> Option<String> {
+    let pattern = format!("/{}/", kind);
+    let pos = href.find(pattern.as_str())?;
+    let id: String = href[pos + pattern.len()..]
+        .chars()
+        .take_while(|c| c.is_ascii_digit())
+        .collect();
+    if id.is_empty() {
+        None
+    } else {
+        Some(id)
+    }
+}
+
+/// The link resolved against the page it is on, `None` when either is not a valid url.
+pub(crate) fn absolute_url(page_url: &str, href: &str) -> Option<String> {
+    Url::parse(page_url)
+        .and_then(|url| url.join(href))
+        .map(|url| url.to_string())
+        .ok()
+}
+
+/// The compiled selector, every selector string is parsed once per run.
+pub(crate) fn get_selector(selector_str: &str) -> Result<Arc<Selector>, ParseError> {
+    if let Some(selector) = SELECTORS
+        .read()
+        .expect("failed to get SELECTORS read lock")
+        .get(selector_str)
+    {
+        return Ok(selector.clone());
+    }
+
+    let selector = Selector::parse(selector_str).map_err(|e| ParseError::Selector {
+        selector: selector_str.to_owned(),
+        reason: format!("{:?}", e),
+    })?;
+    let selector = Arc::new(selector);
+    SELECTORS
+        .write()
+        .expect("failed to get SELECTORS write lock")
+        .insert(selector_str.to_owned(), selector.clone());
+    Ok(selector)
 }
 
 pub(crate) fn node_ref_text(node_ref: NodeRef<Node>) -> Vec<String> {
@@ -31,3 +94,50 @@ pub(crate) fn node_ref_text(node_ref: NodeRef<Node>) -> Vec<String> {
         Vec::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tag_names() {
+        let tag = |url: &str| parse_tag_name(url);
+        assert_eq!(
+            tag("https://book.douban.com/tag/%E5%B0%8F%E8%AF%B4?start=20&type=T").as_deref(),
+            Some("小说")
+        );
+        assert_eq!(
+            tag("https://book.douban.com/tag/小说").as_deref(),
+            Some("小说")
+        );
+        assert_eq!(
+            tag("https://book.douban.com/tag/C%2B%2B/").as_deref(),
+            Some("C++")
+        );
+        // a cut multi-byte sequence
+        assert_eq!(tag("https://book.douban.com/tag/%E5%B0"), None);
+        assert_eq!(tag("https://book.douban.com/tag/"), None);
+        assert_eq!(tag("https://book.douban.com/subject/1/"), None);
+    }
+
+    #[test]
+    fn absolute_urls() {
+        let page = "https://book.douban.com/tag/%E5%B0%8F%E8%AF%B4?start=0&type=T";
+        let cases = [
+            (
+                "?start=20&type=T",
+                "https://book.douban.com/tag/%E5%B0%8F%E8%AF%B4?start=20&type=T",
+            ),
+            (
+                "/tag/小说?start=20",
+                "https://book.douban.com/tag/%E5%B0%8F%E8%AF%B4?start=20",
+            ),
+            ("../subject/1/", "https://book.douban.com/subject/1/"),
+            ("https://www.douban.com/", "https://www.douban.com/"),
+        ];
+        for (href, expected) in cases.iter() {
+            assert_eq!(absolute_url(page, href).as_deref(), Some(*expected));
+        }
+        assert_eq!(absolute_url("/tag/", "?start=20"), None);
+    }
+}
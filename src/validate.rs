@@ -0,0 +1,207 @@
+use crate::book::{Book, IsbnStatus, QualityIssue, QualityReport, RatingState};
+use lazy_static::lazy_static;
+use log::info;
+use std::sync::RwLock;
+
+lazy_static! {
+    static ref BLOCKING_RULES: RwLock<Vec<String>> = RwLock::new(
+        DEFAULT_BLOCKING_RULES
+            .iter()
+            .map(|r| r.to_string())
+            .collect()
+    );
+}
+
+// a book without a title is not worth a file, everything else is only flagged
+const DEFAULT_BLOCKING_RULES: &[&str] = &["missing.title"];
+
+/// Rules whose issues keep a book from being stored, use --block-on=rule1,rule2.
+///
+/// A rule is a full name like "missing.title", or a prefix like "suspicious.*",
+/// `None` keeps the defaults, an empty list blocks nothing.
+pub(crate) fn init(blocking_rules: Option<Vec<String>>) {
+    let blocking_rules = match blocking_rules {
+        Some(v) => v,
+        None => return,
+    };
+    info!("blocking validation rules: {:?}", blocking_rules);
+    *BLOCKING_RULES
+        .write()
+        .expect("failed to get BLOCKING_RULES write lock") = blocking_rules;
+}
+
+fn rule_matches(pattern: &str, rule: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => rule.starts_with(prefix),
+        None => pattern == rule,
+    }
+}
+
+/// The issues of the report that keep the book from being stored.
+pub(crate) fn blocking_issues(report: &QualityReport) -> Vec<QualityIssue> {
+    let blocking_rules = BLOCKING_RULES
+        .read()
+        .expect("failed to get BLOCKING_RULES read lock");
+    report
+        .issues
+        .iter()
+        .filter(|issue| {
+            blocking_rules
+                .iter()
+                .any(|pattern| rule_matches(pattern, issue.rule.as_str()))
+        })
+        .cloned()
+        .collect()
+}
+
+fn issue(rule: &str, detail: String) -> QualityIssue {
+    QualityIssue {
+        rule: rule.to_owned(),
+        detail,
+    }
+}
+
+/// (field, present) for every field douban shows for most books.
+pub(crate) fn field_presence(book: &Book) -> Vec<(&'static str, bool)> {
+    vec![
+        ("title", !book.title.is_empty()),
+        ("author", !book.author.is_empty()),
+        ("press", !book.press.is_empty()),
+        ("publication_date", book.publication_date.is_some()),
+        ("page_count", book.page_count.is_some()),
+        ("price", book.price_value.is_some()),
+        ("isbn", book.isbn_status != IsbnStatus::Missing),
+        ("score", book.score.state != RatingState::ParseFailed),
+        ("cover", !book.cover_url_small.is_empty()),
+        ("tags", !book.tags.is_empty()),
+        ("content_intro", !book.content_intro.is_empty()),
+        ("author_intro", !book.author_intro.is_empty()),
+        ("directory", !book.directory.is_empty()),
+    ]
+}
+
+/// Checks what the parser got out of the page.
+pub(crate) fn check(book: &Book) -> QualityReport {
+    let mut report = QualityReport::default();
+
+    let fields = field_presence(book);
+    report.expected = fields.len();
+    for (field, present) in fields {
+        if present {
+            report.present += 1;
+        } else {
+            report
+                .issues
+                .push(issue(format!("missing.{}", field).as_str(), String::new()));
+        }
+    }
+
+    // the login or the forbidden page parsed as a book
+    if crate::profile::get()
+        .book
+        .texts
+        .banned_title
+        .matches(&book.title)
+    {
+        report
+            .issues
+            .push(issue("suspicious.title", book.title.clone()));
+    }
+    if let Some(date) = book.publication_date {
+        if date.year < 1400 || 2100 < date.year {
+            report
+                .issues
+                .push(issue("suspicious.publication_date", date.to_string()));
+        }
+    }
+    if let Some(page_count) = book.page_count {
+        if 20000 < page_count {
+            report
+                .issues
+                .push(issue("suspicious.page_count", page_count.to_string()));
+        }
+    }
+    if let Some(price) = book.price_value.as_ref() {
+        if price.amount <= 0.0 || 100_000.0 < price.amount {
+            report
+                .issues
+                .push(issue("suspicious.price", price.to_string()));
+        }
+    }
+    if book.isbn_status == IsbnStatus::Invalid {
+        report
+            .issues
+            .push(issue("suspicious.isbn", book.isbn.clone()));
+    }
+    if let Some(score) = book.score.score {
+        if !(0.0..=10.0).contains(&score) {
+            report
+                .issues
+                .push(issue("suspicious.score", score.to_string()));
+        }
+    }
+    for (label, _) in book.unknown_info.iter() {
+        report.issues.push(issue("unknown_label", label.clone()));
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(rules: &[&str]) -> QualityReport {
+        QualityReport {
+            issues: rules.iter().map(|r| issue(r, String::new())).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn blocking_rules(report: &QualityReport) -> Vec<String> {
+        blocking_issues(report)
+            .into_iter()
+            .map(|issue| issue.rule)
+            .collect()
+    }
+
+    #[test]
+    fn rules_match_by_name_or_prefix() {
+        assert!(rule_matches("missing.title", "missing.title"));
+        assert!(!rule_matches("missing.title", "missing.title_extra"));
+        assert!(!rule_matches("missing.title", "missing.author"));
+        assert!(rule_matches("suspicious.*", "suspicious.price"));
+        assert!(!rule_matches("suspicious.*", "missing.price"));
+        assert!(rule_matches("*", "unknown_label"));
+    }
+
+    // one test, the rules are shared by the whole process
+    #[test]
+    fn block_on_narrows_or_widens_the_rules() {
+        let report = report(&["missing.title", "missing.author", "suspicious.price"]);
+
+        // no --block-on keeps the defaults
+        init(None);
+        assert_eq!(blocking_rules(&report), vec!["missing.title"]);
+
+        init(Some(vec![
+            "missing.author".to_owned(),
+            "suspicious.*".to_owned(),
+        ]));
+        assert_eq!(
+            blocking_rules(&report),
+            vec!["missing.author", "suspicious.price"]
+        );
+
+        // --block-on without rules blocks nothing
+        init(Some(Vec::new()));
+        assert!(blocking_rules(&report).is_empty());
+
+        init(Some(
+            DEFAULT_BLOCKING_RULES
+                .iter()
+                .map(|r| r.to_string())
+                .collect(),
+        ));
+    }
+}